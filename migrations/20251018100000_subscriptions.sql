@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS subscriptions (
+    person INTEGER NOT NULL PRIMARY KEY,
+    notify_close INTEGER NOT NULL DEFAULT 0,
+    -- minutes since local midnight
+    quiet_from INTEGER,
+    quiet_to INTEGER
+);
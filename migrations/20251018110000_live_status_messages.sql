@@ -0,0 +1,13 @@
+-- Live status messages can now live in multiple chats and forum topics
+CREATE TABLE IF NOT EXISTS status_messages_new (
+    chat_id INTEGER NOT NULL,
+    -- 0 if the message is not in a forum topic
+    thread_id INTEGER NOT NULL DEFAULT 0,
+    message_id INTEGER NOT NULL,
+    PRIMARY KEY (chat_id, thread_id)
+);
+-- chat_id 0 means the public chat, it is replaced with the configured id on startup
+INSERT INTO status_messages_new (chat_id, thread_id, message_id)
+    SELECT 0, 0, message_id FROM status_messages;
+DROP TABLE status_messages;
+ALTER TABLE status_messages_new RENAME TO status_messages;
@@ -0,0 +1,12 @@
+-- Announcements waiting to be delivered, written in the same transaction as the visit change
+CREATE TABLE IF NOT EXISTS outbox (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    -- 0: CheckIn, 1: Plan, 2: Unplan
+    kind INTEGER NOT NULL,
+    person INTEGER NOT NULL,
+    day INTEGER NOT NULL,
+    purpose TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- unix timestamp
+    next_attempt_at INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,7 @@
+-- Aggregated "today at the space" announcement messages in the public chat
+CREATE TABLE IF NOT EXISTS daily_announcements (
+    day INTEGER NOT NULL PRIMARY KEY,
+    message_id INTEGER NOT NULL,
+    -- announced actions, one per line
+    text TEXT NOT NULL
+);
@@ -0,0 +1,16 @@
+-- people without Telegram accounts, their person id is the negated row id
+CREATE TABLE IF NOT EXISTS guests (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    -- lowercased name, sqlite NOCASE only folds ASCII
+    name_key TEXT NOT NULL UNIQUE,
+    created_by INTEGER NOT NULL
+);
+
+-- resident who changed someone else's visit
+CREATE TABLE IF NOT EXISTS visit_actors (
+    person INTEGER NOT NULL,
+    day INTEGER NOT NULL,
+    actor INTEGER NOT NULL,
+    PRIMARY KEY (person, day)
+);
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    -- unix timestamps
+    starts_at INTEGER NOT NULL,
+    ends_at INTEGER NOT NULL,
+    organizer INTEGER NOT NULL,
+    -- 0 means unlimited
+    capacity INTEGER NOT NULL DEFAULT 0,
+    cancelled INTEGER NOT NULL DEFAULT 0,
+    reminded INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS rsvps (
+    -- sign-up order, people beyond the capacity are on the waitlist
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    event INTEGER NOT NULL,
+    person INTEGER NOT NULL,
+    UNIQUE (event, person)
+);
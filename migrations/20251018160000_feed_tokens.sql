@@ -0,0 +1,5 @@
+-- secret tokens for personal calendar feeds
+CREATE TABLE IF NOT EXISTS feed_tokens (
+    person INTEGER NOT NULL PRIMARY KEY,
+    token TEXT NOT NULL UNIQUE
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS open_sessions (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    opener INTEGER NOT NULL,
+    note TEXT NOT NULL DEFAULT '',
+    -- unix timestamps
+    opened_at INTEGER NOT NULL,
+    closes_at INTEGER NOT NULL,
+    -- NULL while the session is running
+    closed_at INTEGER,
+    reminded INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS closures (
+    -- days from CE, like visit.day
+    day INTEGER NOT NULL PRIMARY KEY,
+    reason TEXT NOT NULL DEFAULT '',
+    author INTEGER NOT NULL
+);
@@ -0,0 +1,12 @@
+-- "can someone open?" requests from guests, residents volunteer to host them
+CREATE TABLE IF NOT EXISTS open_requests (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    requester INTEGER NOT NULL,
+    -- days from CE, like visit.day
+    day INTEGER NOT NULL,
+    -- "HH:MM" or empty if any time works
+    time TEXT NOT NULL DEFAULT '',
+    comment TEXT NOT NULL DEFAULT '',
+    host INTEGER,
+    UNIQUE (requester, day)
+);
@@ -0,0 +1,20 @@
+-- lock-up checklists sent to the last resident leaving
+CREATE TABLE IF NOT EXISTS lockups (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    person INTEGER NOT NULL,
+    -- days from CE, like visit.day
+    day INTEGER NOT NULL,
+    -- bit per checklist item
+    checked INTEGER NOT NULL DEFAULT 0,
+    -- unix timestamp, NULL until confirmed
+    confirmed_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    -- unix timestamp
+    at INTEGER NOT NULL,
+    person INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    details TEXT NOT NULL DEFAULT ''
+);
@@ -0,0 +1,8 @@
+-- a single status note shown on top of the live status
+CREATE TABLE IF NOT EXISTS motd (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    text TEXT NOT NULL,
+    author INTEGER NOT NULL,
+    -- unix timestamp
+    expires_at INTEGER NOT NULL
+);
@@ -0,0 +1,11 @@
+-- notice board shown in the live status
+CREATE TABLE IF NOT EXISTS notices (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    author INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    -- unix timestamps
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS notices_expires_at ON notices (expires_at);
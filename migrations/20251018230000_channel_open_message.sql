@@ -0,0 +1,7 @@
+-- "🟢 Открыто" post in the public channel, edited once the space closes
+CREATE TABLE IF NOT EXISTS channel_open_message (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    message_id INTEGER NOT NULL,
+    -- unix timestamp
+    opened_at INTEGER NOT NULL
+);
@@ -0,0 +1,91 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    /// The last resident leaving got the lock-up checklist
+    LockupStarted,
+    LockupConfirmed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub person: Uid,
+    pub action: AuditAction,
+    pub details: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    pool: SqlitePool,
+}
+
+impl AuditAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::LockupStarted => "lockup_started",
+            AuditAction::LockupConfirmed => "lockup_confirmed",
+        }
+    }
+
+    fn from_str(action: &str) -> Option<Self> {
+        match action {
+            "lockup_started" => Some(AuditAction::LockupStarted),
+            "lockup_confirmed" => Some(AuditAction::LockupConfirmed),
+            _ => None,
+        }
+    }
+}
+
+impl AuditLog {
+    pub fn new(pool: SqlitePool) -> Result<AuditLog> {
+        Ok(AuditLog { pool })
+    }
+
+    /// Meant to be called inside the transaction that makes the recorded change
+    pub async fn record(conn: &mut SqliteConnection, entry: &AuditEntry) -> Result<()> {
+        let at = entry.at.timestamp();
+        let person: i64 = entry.person.into();
+        let action = entry.action.as_str();
+        sqlx::query!(
+            "INSERT INTO audit_log (at, person, action, details) VALUES (?1, ?2, ?3, ?4)",
+            at,
+            person,
+            action,
+            entry.details,
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Ordered by time, entries with actions this version doesn't know are skipped
+    pub async fn get_entries(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AuditEntry>> {
+        let from = from.timestamp();
+        let to = to.timestamp();
+        Ok(sqlx::query!(
+            "SELECT at, person, action, details FROM audit_log WHERE at >= ?1 AND at < ?2 ORDER BY id",
+            from,
+            to,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|r| {
+            Some(AuditEntry {
+                at: DateTime::from_timestamp(r.at, 0).unwrap_or_default(),
+                person: Uid::from(r.person),
+                action: AuditAction::from_str(&r.action)?,
+                details: r.details,
+            })
+        })
+        .collect())
+    }
+}
@@ -1,13 +1,31 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Result;
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use sqlx::SqlitePool;
 use teloxide::types::UserId;
+use tokio::sync::{Notify, watch};
+use tokio_util::sync::CancellationToken;
 
+use crate::audit::{AuditEntry, AuditLog};
+use crate::closures::{Closure, Closures};
 use crate::config::DbConfig;
+use crate::events::{Event, Events, NewEvent, RsvpStatus, Unrsvp};
+use crate::external_calendar::ExternalCalendar;
+use crate::feeds::FeedTokens;
+use crate::guests::Guests;
+use crate::ical::CalendarEvent;
+use crate::lockups::{Lockup, Lockups};
+use crate::motd::{Motd, Motds};
+use crate::notices::{Notice, Notices};
+use crate::open_requests::{HOST_VISIT_PURPOSE, OpenRequest, OpenRequests};
+use crate::open_sessions::{OpenSession, OpenSessions};
+use crate::outbox::{Announcement, Outbox};
+use crate::rate_limit::QueueMetrics;
 use crate::rest_api::RestApi;
-use crate::utils::today;
+use crate::stats::{Stats, compute_stats};
+use crate::subscriptions::{Subscription, Subscriptions};
+use crate::utils::{day_of, today};
 use crate::visits::VisitUpdate;
 use crate::{Config, TelegramBot, Visit, VisitStatus, Visits};
 
@@ -15,8 +33,23 @@ use crate::{Config, TelegramBot, Visit, VisitStatus, Visits};
 pub struct BackendImpl {
     pub pool: SqlitePool,
     pub visits: Visits,
+    pub subscriptions: Subscriptions,
+    pub outbox: Outbox,
+    pub guests: Guests,
+    pub events: Events,
+    pub feed_tokens: FeedTokens,
+    pub external_calendar: Option<ExternalCalendar>,
+    pub open_sessions: OpenSessions,
+    pub closures: Closures,
+    pub open_requests: OpenRequests,
+    pub lockups: Lockups,
+    pub audit_log: AuditLog,
+    pub motds: Motds,
+    pub notices: Notices,
     pub tg_bot: Arc<TelegramBot<Self>>,
     pub rest_api: RestApi<Self>,
+    changes: watch::Sender<()>,
+    outbox_notify: Arc<Notify>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -28,6 +61,13 @@ impl From<i64> for Uid {
     }
 }
 
+impl Uid {
+    /// Guests without Telegram accounts get negative ids
+    pub fn is_guest(&self) -> bool {
+        i64::from(*self) < 0
+    }
+}
+
 impl From<Uid> for i64 {
     fn from(val: Uid) -> Self {
         val.0.0 as i64
@@ -37,27 +77,225 @@ impl From<Uid> for i64 {
 pub trait Backend: Sized + Send + Sync + 'static {
     fn pool(&self) -> &SqlitePool;
 
+    /// Receiver marked as changed every time visits are modified
+    fn subscribe_changes(&self) -> watch::Receiver<()>;
+
+    /// Visit-changing methods return whether the status actually changed
     fn check_in(
         &self,
         person: Uid,
         purpose: Option<String>,
-    ) -> impl Future<Output = Result<()>> + Send;
-    fn check_out(&self, person: Uid) -> impl Future<Output = Result<()>> + Send;
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Only people who checked in today can check out, planned visits stay planned
+    fn check_out(&self, person: Uid) -> impl Future<Output = Result<bool>> + Send;
     fn plan_visit(
         &self,
         person: Uid,
         day: NaiveDate,
         purpose: Option<String>,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    fn unplan_visit(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Changes only the purpose of an existing visit, without announcing anything
+    fn set_purpose(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        purpose: String,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Puts the visit back into `previous` state, `None` meaning there was no visit
+    fn restore_visit(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        previous: Option<Visit>,
     ) -> impl Future<Output = Result<()>> + Send;
-    fn unplan_visit(&self, person: Uid, day: NaiveDate) -> impl Future<Output = Result<()>> + Send;
     fn check_out_everybody(&self) -> impl Future<Output = Result<()>> + Send;
+    fn get_visit(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+    ) -> impl Future<Output = Result<Option<Visit>>> + Send;
+    fn get_person_visits(
+        &self,
+        person: Uid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<Visit>>> + Send;
     fn get_visits(
         &self,
         from: NaiveDate,
         to: NaiveDate,
     ) -> impl Future<Output = Result<Vec<Visit>>> + Send;
+    fn get_stats(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = Result<Stats>> + Send;
+    fn create_event(&self, event: NewEvent) -> impl Future<Output = Result<i64>> + Send;
+    fn get_event(&self, id: i64) -> impl Future<Output = Result<Option<Event>>> + Send;
+    /// Events overlapping the range, cancelled ones excluded
+    fn get_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<Event>>> + Send;
+    /// In sign-up order
+    fn get_event_attendees(&self, id: i64) -> impl Future<Output = Result<Vec<Uid>>> + Send;
+    /// Returns the attendees to notify, `None` if there was nothing to cancel
+    fn cancel_event(&self, id: i64) -> impl Future<Output = Result<Option<Vec<Uid>>>> + Send;
+    /// Returns `None` if the person has already signed up.
+    /// People who get a place also get a planned visit for the day of the event.
+    fn rsvp(
+        &self,
+        event: &Event,
+        person: Uid,
+    ) -> impl Future<Output = Result<Option<RsvpStatus>>> + Send;
+    fn unrsvp(&self, event: &Event, person: Uid) -> impl Future<Output = Result<Unrsvp>> + Send;
+    fn get_due_event_reminders(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<Event>>> + Send;
+    fn mark_event_reminded(&self, id: i64) -> impl Future<Output = Result<()>> + Send;
+    /// Token of the person's private calendar feed, created on first use
+    fn get_feed_token(&self, person: Uid) -> impl Future<Output = Result<String>> + Send;
+    fn reset_feed_token(&self, person: Uid) -> impl Future<Output = Result<String>> + Send;
+    fn get_feed_person(&self, token: String) -> impl Future<Output = Result<Option<Uid>>> + Send;
+    fn guest(&self, name: String, created_by: Uid) -> impl Future<Output = Result<Uid>> + Send;
+    fn guest_name(&self, person: Uid) -> impl Future<Output = Result<Option<String>>> + Send;
+    /// Remembers who last changed a visit on behalf of `person`, `None` if they did it themselves
+    fn set_actor(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        actor: Option<Uid>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn get_actor(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+    ) -> impl Future<Output = Result<Option<Uid>>> + Send;
+    /// Returns the newly closed days
+    fn add_closures(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        reason: String,
+        author: Uid,
+    ) -> impl Future<Output = Result<Vec<NaiveDate>>> + Send;
+    /// Returns the number of days that were closed
+    fn remove_closures(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = Result<u64>> + Send;
+    fn get_closures(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<Closure>>> + Send;
+    /// Also plans a visit for the requester unless they already have one for the day.
+    /// Returns `None` if the person has already asked for that day.
+    fn create_open_request(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+        time: Option<NaiveTime>,
+        comment: String,
+    ) -> impl Future<Output = Result<Option<OpenRequest>>> + Send;
+    fn get_open_request(&self, id: i64)
+    -> impl Future<Output = Result<Option<OpenRequest>>> + Send;
+    fn get_open_requests(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<OpenRequest>>> + Send;
+    /// Also unplans the visit planned by `create_open_request`, returns the removed request
+    fn cancel_open_request(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+    ) -> impl Future<Output = Result<Option<OpenRequest>>> + Send;
+    /// Returns false if somebody is already hosting.
+    /// The host gets a planned visit for the day unless they already have one.
+    fn host_open_request(
+        &self,
+        request: &OpenRequest,
+        host: Uid,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Replaces the current status note
+    fn set_motd(&self, motd: Motd) -> impl Future<Output = Result<()>> + Send;
+    /// Returns false if there was no note
+    fn clear_motd(&self) -> impl Future<Output = Result<bool>> + Send;
+    /// `None` if there is no note or it has expired
+    fn get_motd(&self) -> impl Future<Output = Result<Option<Motd>>> + Send;
+    fn add_notice(
+        &self,
+        author: Uid,
+        text: String,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<i64>> + Send;
+    /// Only the author can remove a notice
+    fn remove_notice(&self, id: i64, author: Uid) -> impl Future<Output = Result<bool>> + Send;
+    /// Notices that haven't expired yet, oldest first
+    fn get_notices(&self) -> impl Future<Output = Result<Vec<Notice>>> + Send;
+    /// Lock-up checklist for the last resident leaving, `details` go into the audit log
+    fn start_lockup(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        details: String,
+    ) -> impl Future<Output = Result<i64>> + Send;
+    fn get_lockup(&self, id: i64) -> impl Future<Output = Result<Option<Lockup>>> + Send;
+    /// Return false if the checklist is already confirmed
+    fn toggle_lockup_item(&self, id: i64, item: u8) -> impl Future<Output = Result<bool>> + Send;
+    fn confirm_lockup(&self, id: i64, details: String)
+    -> impl Future<Output = Result<bool>> + Send;
+    fn get_audit_entries(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<AuditEntry>>> + Send;
+    /// Declares the space open to guests until `closes_at`, replacing the running session
+    fn open_space(
+        &self,
+        opener: Uid,
+        note: String,
+        closes_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<OpenSession>> + Send;
+    /// The most recent session, check `is_open` to see if it is still running
+    fn get_open_session(&self) -> impl Future<Output = Result<Option<OpenSession>>> + Send;
+    /// Return false if the session is not running anymore
+    fn close_open_session(&self, id: i64) -> impl Future<Output = Result<bool>> + Send;
+    fn extend_open_session(
+        &self,
+        id: i64,
+        closes_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    fn get_due_open_session_reminders(
+        &self,
+        until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<OpenSession>>> + Send;
+    fn mark_open_session_reminded(&self, id: i64) -> impl Future<Output = Result<()>> + Send;
+    /// Returns the sessions that were closed
+    fn close_expired_open_sessions(&self) -> impl Future<Output = Result<Vec<OpenSession>>> + Send;
+    fn subscribe(&self, subscription: Subscription) -> impl Future<Output = Result<()>> + Send;
+    fn unsubscribe(&self, person: Uid) -> impl Future<Output = Result<bool>> + Send;
+    fn get_subscriptions(&self) -> impl Future<Output = Result<Vec<Subscription>>> + Send;
+    fn telegram_queue_metrics(&self) -> QueueMetrics;
+    /// Events of the imported calendar overlapping the range, empty if there is none
+    fn get_external_events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent>;
 }
 
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const OUTBOX_BASE_BACKOFF_SECS: i64 = 10;
+const OUTBOX_MAX_BACKOFF_SECS: i64 = 60 * 60;
+const OUTBOX_MAX_ATTEMPTS: i64 = 20;
+
 fn maybe_panic(text: &str) -> Result<()> {
     match text {
         "panic" => panic!("ayaya"),
@@ -71,7 +309,11 @@ impl Backend for BackendImpl {
         &self.pool
     }
 
-    async fn check_in(&self, person: Uid, purpose: Option<String>) -> Result<()> {
+    fn subscribe_changes(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+
+    async fn check_in(&self, person: Uid, purpose: Option<String>) -> Result<bool> {
         let visit_update = VisitUpdate {
             person,
             day: today(),
@@ -79,29 +321,33 @@ impl Backend for BackendImpl {
             status: VisitStatus::CheckedIn,
         };
 
-        let updated = self.visits.upsert_visit(&visit_update).await?;
+        let updated = self
+            .visits
+            .upsert_visit_announced(&visit_update, &Announcement::CheckIn(visit_update.clone()))
+            .await?;
+        self.notify_changed();
 
         if updated {
-            self.tg_bot.announce_check_in(&visit_update).await?;
+            self.outbox_notify.notify_one();
         }
 
-        Ok(())
+        Ok(updated)
     }
 
-    async fn check_out(&self, person: Uid) -> Result<()> {
-        let visit_update = VisitUpdate {
-            person,
-            day: today(),
-            purpose: None,
-            status: VisitStatus::CheckedOut,
-        };
-
-        self.visits.upsert_visit(&visit_update).await?;
-
-        Ok(())
+    async fn check_out(&self, person: Uid) -> Result<bool> {
+        let updated = self.visits.check_out(person, today()).await?;
+        if updated {
+            self.notify_changed();
+        }
+        Ok(updated)
     }
 
-    async fn plan_visit(&self, person: Uid, day: NaiveDate, purpose: Option<String>) -> Result<()> {
+    async fn plan_visit(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        purpose: Option<String>,
+    ) -> Result<bool> {
         let visit_update = VisitUpdate {
             person,
             day,
@@ -109,35 +355,461 @@ impl Backend for BackendImpl {
             status: VisitStatus::Planned,
         };
 
-        let updated = self.visits.upsert_visit(&visit_update).await?;
+        let updated = self
+            .visits
+            .upsert_visit_announced(&visit_update, &Announcement::Plan(visit_update.clone()))
+            .await?;
+        self.notify_changed();
 
         if updated {
-            self.tg_bot.announce_plan(&visit_update).await?;
+            self.outbox_notify.notify_one();
         }
 
         maybe_panic(visit_update.purpose.as_deref().unwrap_or_default())?;
 
-        Ok(())
+        Ok(updated)
     }
 
-    async fn unplan_visit(&self, person: Uid, day: NaiveDate) -> Result<()> {
-        let deleted = self.visits.delete_visit(person, day).await?;
+    async fn unplan_visit(&self, person: Uid, day: NaiveDate) -> Result<bool> {
+        let deleted = self
+            .visits
+            .delete_visit_announced(person, day, &Announcement::Unplan { person, day })
+            .await?;
+        // nobody needs to open the space for a guest who isn't coming
+        self.withdraw_open_request(person, day).await?;
+        self.notify_changed();
 
         if deleted {
-            self.tg_bot.announce_unplan(person, day).await?;
+            self.outbox_notify.notify_one();
         }
 
+        Ok(deleted)
+    }
+
+    async fn set_purpose(&self, person: Uid, day: NaiveDate, purpose: String) -> Result<bool> {
+        let updated = self.visits.update_purpose(person, day, &purpose).await?;
+        if updated {
+            self.notify_changed();
+        }
+        Ok(updated)
+    }
+
+    async fn restore_visit(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        previous: Option<Visit>,
+    ) -> Result<()> {
+        match previous {
+            None => {
+                let current = self.visits.get_visit(person, day).await?;
+                if current.is_some_and(|v| v.status == VisitStatus::Planned) {
+                    // Announces the cancellation, since the plan itself was most likely announced already
+                    self.unplan_visit(person, day).await?;
+                } else {
+                    // "больше не планирует зайти" would be wrong for an undone check-in
+                    self.visits.delete_visit(person, day).await?;
+                    self.notify_changed();
+                }
+            }
+            Some(visit) => {
+                self.visits
+                    .upsert_visit(&VisitUpdate {
+                        person,
+                        day,
+                        purpose: Some(visit.purpose),
+                        status: visit.status,
+                    })
+                    .await?;
+                self.notify_changed();
+            }
+        }
         Ok(())
     }
 
     async fn check_out_everybody(&self) -> Result<()> {
         self.visits.check_out_everybody(today()).await?;
+        self.notify_changed();
         Ok(())
     }
 
+    async fn get_visit(&self, person: Uid, day: NaiveDate) -> Result<Option<Visit>> {
+        self.visits.get_visit(person, day).await
+    }
+
+    async fn get_person_visits(
+        &self,
+        person: Uid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Visit>> {
+        self.visits.get_person_visits(person, from, to).await
+    }
+
     async fn get_visits(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Visit>> {
         self.visits.get_visits(from, to).await
     }
+
+    async fn get_stats(&self, from: NaiveDate, to: NaiveDate) -> Result<Stats> {
+        // nothing is kept before that, the period shown shouldn't pretend otherwise
+        let from = from.max(self.visits.history_start(today()));
+        let visits = self.visits.get_visits(from, to).await?;
+        let residents = self
+            .tg_bot
+            .residents(visits.iter().map(|v| v.person))
+            .await?;
+        Ok(compute_stats(&visits, &residents, from, to))
+    }
+
+    async fn create_event(&self, event: NewEvent) -> Result<i64> {
+        let id = self.events.create(&event).await?;
+        self.notify_changed();
+        Ok(id)
+    }
+
+    async fn get_event(&self, id: i64) -> Result<Option<Event>> {
+        self.events.get(id).await
+    }
+
+    async fn get_events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Event>> {
+        self.events.get_events(from, to).await
+    }
+
+    async fn get_event_attendees(&self, id: i64) -> Result<Vec<Uid>> {
+        self.events.get_attendees(id).await
+    }
+
+    async fn cancel_event(&self, id: i64) -> Result<Option<Vec<Uid>>> {
+        let Some(event) = self.events.get(id).await? else {
+            return Ok(None);
+        };
+        if !self.events.cancel(id).await? {
+            return Ok(None);
+        }
+        let attendees = self.events.get_attendees(id).await?;
+        let (going, _) = event.split_attendees(&attendees);
+        for person in going {
+            self.unplan_event_visit(&event, *person).await?;
+        }
+        self.notify_changed();
+        Ok(Some(attendees))
+    }
+
+    async fn rsvp(&self, event: &Event, person: Uid) -> Result<Option<RsvpStatus>> {
+        if !self.events.add_attendee(event.id, person).await? {
+            return Ok(None);
+        }
+        let attendees = self.events.get_attendees(event.id).await?;
+        let status = event.status_of(&attendees, person);
+        if status == Some(RsvpStatus::Going) {
+            self.plan_event_visit(event, person).await?;
+        }
+        self.notify_changed();
+        Ok(status)
+    }
+
+    async fn unrsvp(&self, event: &Event, person: Uid) -> Result<Unrsvp> {
+        let attendees = self.events.get_attendees(event.id).await?;
+        let status = event.status_of(&attendees, person);
+        if !self.events.remove_attendee(event.id, person).await? {
+            return Ok(Unrsvp {
+                removed: false,
+                promoted: None,
+            });
+        }
+
+        let mut promoted = None;
+        if status == Some(RsvpStatus::Going) {
+            self.unplan_event_visit(event, person).await?;
+            let (_, waitlist) = event.split_attendees(&attendees);
+            promoted = waitlist.first().copied();
+            if let Some(promoted) = promoted {
+                self.plan_event_visit(event, promoted).await?;
+            }
+        }
+        self.notify_changed();
+        Ok(Unrsvp {
+            removed: true,
+            promoted,
+        })
+    }
+
+    async fn get_due_event_reminders(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Event>> {
+        self.events.get_due_reminders(now, until).await
+    }
+
+    async fn mark_event_reminded(&self, id: i64) -> Result<()> {
+        self.events.mark_reminded(id).await
+    }
+
+    async fn get_feed_token(&self, person: Uid) -> Result<String> {
+        self.feed_tokens.get_or_create(person).await
+    }
+
+    async fn reset_feed_token(&self, person: Uid) -> Result<String> {
+        self.feed_tokens.reset(person).await
+    }
+
+    async fn get_feed_person(&self, token: String) -> Result<Option<Uid>> {
+        self.feed_tokens.get_person(&token).await
+    }
+
+    async fn guest(&self, name: String, created_by: Uid) -> Result<Uid> {
+        self.guests.get_or_create(&name, created_by).await
+    }
+
+    async fn guest_name(&self, person: Uid) -> Result<Option<String>> {
+        self.guests.get_name(person).await
+    }
+
+    async fn set_actor(&self, person: Uid, day: NaiveDate, actor: Option<Uid>) -> Result<()> {
+        self.guests.set_actor(person, day, actor).await
+    }
+
+    async fn get_actor(&self, person: Uid, day: NaiveDate) -> Result<Option<Uid>> {
+        self.guests.get_actor(person, day).await
+    }
+
+    async fn subscribe(&self, subscription: Subscription) -> Result<()> {
+        self.subscriptions.upsert_subscription(&subscription).await
+    }
+
+    async fn unsubscribe(&self, person: Uid) -> Result<bool> {
+        self.subscriptions.delete_subscription(person).await
+    }
+
+    async fn add_closures(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        reason: String,
+        author: Uid,
+    ) -> Result<Vec<NaiveDate>> {
+        let added = self.closures.add(from, to, &reason, author).await?;
+        self.notify_changed();
+        Ok(added)
+    }
+
+    async fn remove_closures(&self, from: NaiveDate, to: NaiveDate) -> Result<u64> {
+        let removed = self.closures.remove(from, to).await?;
+        if removed > 0 {
+            self.notify_changed();
+        }
+        Ok(removed)
+    }
+
+    async fn get_closures(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Closure>> {
+        self.closures.get_closures(from, to).await
+    }
+
+    async fn create_open_request(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+        time: Option<NaiveTime>,
+        comment: String,
+    ) -> Result<Option<OpenRequest>> {
+        let Some(id) = self
+            .open_requests
+            .create(requester, day, time, &comment)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if self.visits.get_visit(requester, day).await?.is_none() {
+            self.plan_visit(requester, day, Some(comment.clone()))
+                .await?;
+        }
+        self.notify_changed();
+        Ok(Some(OpenRequest {
+            id,
+            requester,
+            day,
+            time,
+            comment,
+            host: None,
+        }))
+    }
+
+    async fn get_open_request(&self, id: i64) -> Result<Option<OpenRequest>> {
+        self.open_requests.get(id).await
+    }
+
+    async fn get_open_requests(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<OpenRequest>> {
+        self.open_requests.get_requests(from, to).await
+    }
+
+    async fn cancel_open_request(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+    ) -> Result<Option<OpenRequest>> {
+        let Some(request) = self.withdraw_open_request(requester, day).await? else {
+            return Ok(None);
+        };
+        if self
+            .visits
+            .get_visit(requester, day)
+            .await?
+            .is_some_and(|v| v.status == VisitStatus::Planned && v.purpose == request.comment)
+        {
+            self.unplan_visit(requester, day).await?;
+        }
+        self.notify_changed();
+        Ok(Some(request))
+    }
+
+    async fn host_open_request(&self, request: &OpenRequest, host: Uid) -> Result<bool> {
+        if !self.open_requests.set_host(request.id, host).await? {
+            return Ok(false);
+        }
+        if self.visits.get_visit(host, request.day).await?.is_none() {
+            self.plan_visit(host, request.day, Some(HOST_VISIT_PURPOSE.to_owned()))
+                .await?;
+        }
+        self.notify_changed();
+        Ok(true)
+    }
+
+    async fn set_motd(&self, motd: Motd) -> Result<()> {
+        self.motds.set(&motd).await?;
+        self.notify_changed();
+        Ok(())
+    }
+
+    async fn clear_motd(&self) -> Result<bool> {
+        let cleared = self.motds.clear().await?;
+        if cleared {
+            self.notify_changed();
+        }
+        Ok(cleared)
+    }
+
+    async fn get_motd(&self) -> Result<Option<Motd>> {
+        self.motds.get(Utc::now()).await
+    }
+
+    async fn add_notice(
+        &self,
+        author: Uid,
+        text: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<i64> {
+        let id = self
+            .notices
+            .add(author, &text, Utc::now(), expires_at)
+            .await?;
+        self.notify_changed();
+        Ok(id)
+    }
+
+    async fn remove_notice(&self, id: i64, author: Uid) -> Result<bool> {
+        let removed = self.notices.remove(id, author).await?;
+        if removed {
+            self.notify_changed();
+        }
+        Ok(removed)
+    }
+
+    async fn get_notices(&self) -> Result<Vec<Notice>> {
+        self.notices.get_notices(Utc::now()).await
+    }
+
+    async fn start_lockup(&self, person: Uid, day: NaiveDate, details: String) -> Result<i64> {
+        self.lockups.create(person, day, Utc::now(), &details).await
+    }
+
+    async fn get_lockup(&self, id: i64) -> Result<Option<Lockup>> {
+        self.lockups.get(id).await
+    }
+
+    async fn toggle_lockup_item(&self, id: i64, item: u8) -> Result<bool> {
+        self.lockups.toggle(id, item).await
+    }
+
+    async fn confirm_lockup(&self, id: i64, details: String) -> Result<bool> {
+        self.lockups.confirm(id, Utc::now(), &details).await
+    }
+
+    async fn get_audit_entries(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AuditEntry>> {
+        self.audit_log.get_entries(from, to).await
+    }
+
+    async fn open_space(
+        &self,
+        opener: Uid,
+        note: String,
+        closes_at: DateTime<Utc>,
+    ) -> Result<OpenSession> {
+        let session = self
+            .open_sessions
+            .open(opener, &note, Utc::now(), closes_at)
+            .await?;
+        self.notify_changed();
+        Ok(session)
+    }
+
+    async fn get_open_session(&self) -> Result<Option<OpenSession>> {
+        self.open_sessions.get_latest().await
+    }
+
+    async fn close_open_session(&self, id: i64) -> Result<bool> {
+        let closed = self.open_sessions.close(id, Utc::now()).await?;
+        if closed {
+            self.notify_changed();
+        }
+        Ok(closed)
+    }
+
+    async fn extend_open_session(&self, id: i64, closes_at: DateTime<Utc>) -> Result<bool> {
+        let extended = self.open_sessions.extend(id, closes_at).await?;
+        if extended {
+            self.notify_changed();
+        }
+        Ok(extended)
+    }
+
+    async fn get_due_open_session_reminders(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<OpenSession>> {
+        self.open_sessions.get_due_reminders(until).await
+    }
+
+    async fn mark_open_session_reminded(&self, id: i64) -> Result<()> {
+        self.open_sessions.mark_reminded(id).await
+    }
+
+    async fn close_expired_open_sessions(&self) -> Result<Vec<OpenSession>> {
+        let closed = self.open_sessions.close_expired(Utc::now()).await?;
+        if !closed.is_empty() {
+            self.notify_changed();
+        }
+        Ok(closed)
+    }
+
+    async fn get_subscriptions(&self) -> Result<Vec<Subscription>> {
+        self.subscriptions.get_subscriptions().await
+    }
+
+    fn telegram_queue_metrics(&self) -> QueueMetrics {
+        self.tg_bot.queue_metrics()
+    }
+
+    fn get_external_events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent> {
+        self.external_calendar
+            .as_ref()
+            .map(|c| c.get_events(from, to))
+            .unwrap_or_default()
+    }
 }
 
 pub async fn connect_db(db_config: &DbConfig) -> Result<SqlitePool> {
@@ -150,25 +822,173 @@ impl BackendImpl {
 
         let pool = connect_db(&config.db).await?;
 
-        let visits = Visits::new(pool.clone())?;
+        let visits = Visits::new(pool.clone())?.with_history_days(config.db.visit_history_days);
+        let subscriptions = Subscriptions::new(pool.clone())?;
+        let outbox = Outbox::new(pool.clone())?;
+        let guests = Guests::new(pool.clone())?;
+        let events = Events::new(pool.clone())?;
+        let feed_tokens = FeedTokens::new(pool.clone())?;
+        let external_calendar = config.external_calendar.map(ExternalCalendar::new);
+        let open_sessions = OpenSessions::new(pool.clone())?;
+        let closures = Closures::new(pool.clone())?;
+        let open_requests = OpenRequests::new(pool.clone())?;
+        let lockups = Lockups::new(pool.clone())?;
+        let audit_log = AuditLog::new(pool.clone())?;
+        let motds = Motds::new(pool.clone())?;
+        let notices = Notices::new(pool.clone())?;
 
         sqlx::migrate!("./migrations").run(&pool).await?;
 
         let backend = Arc::new_cyclic(|backend| BackendImpl {
             pool,
             visits,
+            subscriptions,
+            outbox,
+            guests,
+            events,
+            feed_tokens,
+            external_calendar,
+            open_sessions,
+            closures,
+            open_requests,
+            lockups,
+            audit_log,
+            motds,
+            notices,
             tg_bot: TelegramBot::new(config.telegram_bot, backend.clone()).unwrap(),
             rest_api: RestApi::new(config.rest_api, backend.clone()),
+            changes: watch::Sender::new(()),
+            outbox_notify: Arc::new(Notify::new()),
         });
 
         Ok(backend)
     }
 
+    /// Deletes the request and lets the host know they don't need to come for it
+    async fn withdraw_open_request(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+    ) -> Result<Option<OpenRequest>> {
+        let Some(request) = self.open_requests.remove(requester, day).await? else {
+            return Ok(None);
+        };
+        if request.host.is_some()
+            && let Err(e) = self.tg_bot.announce_open_request_withdrawn(&request).await
+        {
+            log::warn!("Failed to tell the host about {:?}: {:?}", request, e);
+        }
+        Ok(Some(request))
+    }
+
+    /// Plans a visit for the day of the event, unless the person already has one
+    async fn plan_event_visit(&self, event: &Event, person: Uid) -> Result<()> {
+        let day = day_of(event.starts_at);
+        if self.visits.get_visit(person, day).await?.is_none() {
+            self.plan_visit(person, day, Some(event.title.clone()))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the visit planned by `plan_event_visit`, leaving visits planned separately alone
+    async fn unplan_event_visit(&self, event: &Event, person: Uid) -> Result<()> {
+        let day = day_of(event.starts_at);
+        if self
+            .visits
+            .get_visit(person, day)
+            .await?
+            .is_some_and(|v| v.status == VisitStatus::Planned && v.purpose == event.title)
+        {
+            self.unplan_visit(person, day).await?;
+        }
+        Ok(())
+    }
+
+    fn notify_changed(&self) {
+        self.changes.send_replace(());
+    }
+
+    async fn deliver_announcement(&self, announcement: &Announcement) -> Result<()> {
+        match announcement {
+            Announcement::CheckIn(visit_update) => {
+                self.tg_bot.announce_check_in(visit_update).await
+            }
+            Announcement::Plan(visit_update) => self.tg_bot.announce_plan(visit_update).await,
+            Announcement::Unplan { person, day } => {
+                self.tg_bot.announce_unplan(*person, *day).await
+            }
+        }
+    }
+
+    async fn deliver_outbox(&self) -> Result<()> {
+        let now = crate::utils::now().timestamp();
+        for entry in self.outbox.get_due(now).await? {
+            match self.deliver_announcement(&entry.announcement).await {
+                Ok(()) => self.outbox.delete(entry.id).await?,
+                Err(e) if entry.attempts + 1 >= OUTBOX_MAX_ATTEMPTS => {
+                    log::error!("Giving up on announcement {:?}: {:?}", entry, e);
+                    self.outbox.delete(entry.id).await?;
+                }
+                Err(e) => {
+                    log::warn!("Failed to deliver announcement {:?}: {:?}", entry, e);
+                    let backoff = OUTBOX_BASE_BACKOFF_SECS << entry.attempts.min(10);
+                    self.outbox
+                        .reschedule(entry.id, now + backoff.min(OUTBOX_MAX_BACKOFF_SECS))
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn outbox_loop(self: Arc<Self>, ct: CancellationToken) {
+        let mut interval = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.outbox_notify.notified() => {}
+                _ = ct.cancelled() => { break }
+            };
+            if let Err(e) = self.deliver_outbox().await {
+                log::error!("Error delivering outbox: {:?}", e);
+            }
+        }
+    }
+
+    async fn external_calendar_loop(self: Arc<Self>, ct: CancellationToken) {
+        let Some(calendar) = &self.external_calendar else {
+            return;
+        };
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(calendar.config().reload_interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = ct.cancelled() => { break }
+            };
+            match calendar.reload().await {
+                Ok(true) => self.notify_changed(),
+                Ok(false) => {}
+                Err(e) => log::error!("Error loading external calendar: {:?}", e),
+            }
+        }
+    }
+
     pub async fn run(self: Arc<Self>) -> Result<()> {
+        let ct = CancellationToken::new();
+        let ct_wait = ct.clone();
+        tokio::spawn(async move {
+            tokio::signal::ctrl_c().await.unwrap();
+            ct_wait.cancel();
+        });
+
         let results = tokio::try_join!(
             tokio::spawn(self.visits.clone().run()),
             tokio::spawn(self.tg_bot.clone().run()),
-            tokio::spawn(self.rest_api.clone().run())
+            tokio::spawn(self.rest_api.clone().run()),
+            tokio::spawn(self.clone().outbox_loop(ct.clone())),
+            tokio::spawn(self.clone().external_calendar_loop(ct))
         )?;
         results.1?;
         results.2?;
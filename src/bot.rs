@@ -1,33 +1,57 @@
 use anyhow::Result;
-use chrono::{Locale, NaiveDate, TimeDelta};
+use chrono::{
+    DateTime, Datelike, Locale, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc,
+};
+use chrono_tz::Tz;
 use futures::FutureExt;
 use itertools::Itertools;
 use sqlx::SqlitePool;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     panic::AssertUnwindSafe,
-    sync::{Arc, RwLock, Weak},
-    time::Duration,
+    sync::{
+        Arc, Mutex, RwLock, Weak,
+        atomic::{AtomicI32, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 use tokio_util::sync::CancellationToken;
 
 use teloxide::{
+    ApiError, RequestError,
     payloads::{SendMessage, SendMessageSetters as _},
     prelude::*,
-    requests::{HasPayload as _, JsonRequest},
+    requests::{HasPayload as _, JsonRequest, Output, Request},
     sugar::request::{RequestLinkPreviewExt as _, RequestReplyExt as _},
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, ReactionType},
+    types::{
+        InlineKeyboardButton, InlineKeyboardMarkup, MessageEntityKind, MessageId, ParseMode,
+        ReactionType, ThreadId,
+    },
     utils::command::BotCommands,
 };
 
 use crate::{
+    audit::AuditAction,
     backend::Backend,
+    callback::{CallbackData, VisitsFilter, VisitsQuery, VisitsView},
+    closures::Closure,
     config::TelegramBotConfig,
+    events::{Event, NewEvent, RsvpStatus},
+    ical::{CalendarEvent, CalendarTime},
+    lockups::Lockup,
+    motd::Motd,
+    open_requests::{HOST_VISIT_PURPOSE, OpenRequest},
+    rate_limit::{Priority, QueueMetrics, RateLimiter},
+    stats::{Stats, StatsPeriod},
+    subscriptions::{QuietHours, Subscription},
     visits::{Visit, VisitStatus, VisitUpdate},
 };
-use crate::{backend::Uid, utils::today};
+use crate::{
+    backend::Uid,
+    utils::{day_of, from_local, next_daily, next_weekly, to_local, today},
+};
 
-#[derive(BotCommands, Clone, Copy)]
+#[derive(BotCommands, Clone, Copy, PartialEq, Eq)]
 #[command(rename_rule = "lowercase")]
 enum Command {
     #[command(
@@ -36,26 +60,84 @@ enum Command {
     PostLive,
     #[command(description = "ℹ️ Посмотреть что сейчас происходит в хакспейсе")]
     Status,
-    #[command(description = "🗓️ Посмотреть кто собирается в хакспейс в ближайшие дни")]
+    #[command(
+        description = "🗓️ Посмотреть кто собирается в хакспейс в ближайшие дни (опционально \"месяц\", \"резиденты\", \"мои\", слово из описания или реплай на человека)"
+    )]
     GetVisits,
     #[command(
-        description = "🗓️ Запланировать зайти в хакспейс (опционально дата в формате YYYY-MM-DD и описание зачем)"
+        description = "🗓️ Запланировать зайти в хакспейс (опционально дата в формате YYYY-MM-DD и описание зачем; резиденты могут реплайнуть, упомянуть человека или написать \"гость Имя:\")"
     )]
     PlanVisit,
     #[command(
         description = "🤔 Передумать заходить в хакспейс (опционально дата в формате YYYY-MM-DD)"
     )]
     UnplanVisit,
-    #[command(description = "👷 Отметиться как зашедший (опционально описание зачем)")]
+    #[command(
+        description = "👷 Отметиться как зашедший (опционально описание зачем; резиденты могут реплайнуть, упомянуть человека или написать \"гость Имя:\")"
+    )]
     CheckIn,
-    #[command(description = "🌆 Отметиться как ушедший")]
+    #[command(
+        description = "✏️ Поменять описание визита, не трогая статус (опционально дата в формате YYYY-MM-DD)"
+    )]
+    Purpose,
+    #[command(
+        description = "🎪 События: \"create YYYY-MM-DD HH:MM-HH:MM [мест:N] Название\" (описание с новой строки, только резиденты), \"list\", \"cancel N\" или номер события чтобы записаться"
+    )]
+    Event,
+    #[command(
+        description = "🚧 Закрытые дни: \"add YYYY-MM-DD [YYYY-MM-DD] причина\", \"remove YYYY-MM-DD [YYYY-MM-DD]\" (только резиденты) или список без аргументов"
+    )]
+    Closure,
+    #[command(
+        description = "📅 Ссылки на календарь спейса и твоих планов (в личке, \"сбросить\" чтобы получить новую личную ссылку)"
+    )]
+    Calendar,
+    #[command(description = "👤 Мои планы, визиты и статистика")]
+    Me,
+    #[command(
+        description = "📊 Статистика посещений (опционально \"неделя\", \"месяц\" или \"год\")"
+    )]
+    Stats,
+    #[command(
+        description = "🌆 Отметиться как ушедший (резиденты могут реплайнуть, упомянуть человека или написать \"гость Имя\")"
+    )]
     CheckOut,
+    #[command(
+        description = "🙋 Попросить резидентов открыть спейс (опционально дата в формате YYYY-MM-DD, время HH:MM и комментарий; \"отменить [дата]\" чтобы отозвать просьбу)"
+    )]
+    AskOpen,
+    #[command(
+        description = "🟢 Открыть спейс для гостей до указанного времени: \"до 23:00\" и опционально заметка (только резиденты)"
+    )]
+    Open,
+    #[command(
+        description = "📣 Заметка сверху статуса (резиденты): опционально \"до 23:00\", \"до YYYY-MM-DD\" или \"на 3ч\" и текст, \"убрать\" чтобы удалить"
+    )]
+    Motd,
+    #[command(
+        description = "📌 Повесить объявление (резиденты): опционально \"до YYYY-MM-DD\", \"до 23:00\" или \"на 3ч\" и текст, по умолчанию на неделю"
+    )]
+    Notice,
+    #[command(description = "📋 Доска объявлений")]
+    Notices,
     #[command(description = "🌒 Закрыть хакспейс")]
     Close,
-    #[command(description = "🔃 Сделать закреп с текущей информацией о спейсе")]
+    #[command(description = "🧾 Журнал закрытий спейса за неделю (только резиденты)")]
+    Audit,
+    #[command(
+        description = "🔃 Сделать закреп с текущей информацией о спейсе в этом чате (или \"канал\" для канала)"
+    )]
     LiveStatus,
-    #[command(description = "🧟 Убрать закреп с текущей информацией о спейсе")]
+    #[command(
+        description = "🧟 Убрать закреп с текущей информацией о спейсе в этом чате (или \"канал\" для канала)"
+    )]
     UnLiveStatus,
+    #[command(
+        description = "🔔 Подписаться на уведомления об открытии спейса (в личке, опционально \"закрытие\" и тихие часы в формате 23:00-09:00)"
+    )]
+    Subscribe,
+    #[command(description = "🔕 Отписаться от уведомлений об открытии спейса")]
+    Unsubscribe,
 }
 
 fn strip_command(text: &str) -> &str {
@@ -66,35 +148,268 @@ fn strip_command(text: &str) -> &str {
     }
 }
 
-fn parse_day_purpose(text: &str) -> (NaiveDate, &str) {
+/// Relative and missing dates are resolved against `today`, the day the message was sent
+pub fn parse_day_purpose(text: &str, today: NaiveDate) -> (NaiveDate, &str) {
     if let Some(purpose) = text.strip_prefix("завтра") {
-        return (today() + TimeDelta::days(1), purpose.trim());
+        return (today + TimeDelta::days(1), purpose.trim());
     }
     if let Some(purpose) = text.strip_prefix("послезавтра") {
-        return (today() + TimeDelta::days(2), purpose.trim());
+        return (today + TimeDelta::days(2), purpose.trim());
     }
 
     let Ok((date, purpose)) = NaiveDate::parse_and_remainder(text, "%Y-%m-%d") else {
-        return (today(), text.trim());
+        return (today, text.trim());
     };
 
     (date, purpose.trim())
 }
 
+fn optional_text(text: &str) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_owned())
+    }
+}
+
 fn parse_visit_text(author: Uid, msg: &str) -> VisitUpdate {
-    let (day, purpose) = parse_day_purpose(msg);
+    let (day, purpose) = parse_day_purpose(msg, today());
     VisitUpdate {
         person: author,
         day,
-        purpose: if purpose.is_empty() {
-            None
-        } else {
-            Some(purpose.to_owned())
-        },
+        purpose: optional_text(purpose),
         status: VisitStatus::Planned,
     }
 }
 
+/// Parses `YYYY-MM-DD [YYYY-MM-DD] причина`, a single date meaning a single day
+fn parse_day_range(text: &str) -> Option<(NaiveDate, NaiveDate, &str)> {
+    let (from, rest) = NaiveDate::parse_and_remainder(text.trim(), "%Y-%m-%d").ok()?;
+    let rest = rest.trim_start();
+    let (to, reason) = NaiveDate::parse_and_remainder(rest, "%Y-%m-%d").unwrap_or((from, rest));
+    if to < from || to - from > TimeDelta::days(CLOSURE_MAX_DAYS) {
+        return None;
+    }
+    Some((from, to, reason.trim()))
+}
+
+/// Splits an optional leading `HH:MM` off the text
+fn parse_optional_time(text: &str) -> (Option<NaiveTime>, &str) {
+    let (first, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    match NaiveTime::parse_from_str(first, "%H:%M") {
+        Ok(time) => (Some(time), rest.trim()),
+        Err(_) => (None, text),
+    }
+}
+
+/// Parses `[до 23:00|до YYYY-MM-DD|на 3ч] текст` of /motd and /notice, texts without a time last `default`
+fn parse_expiry(text: &str, now: DateTime<Tz>, default: TimeDelta) -> (DateTime<Utc>, &str) {
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix("до ").map(str::trim_start) {
+        if let Ok((date, note)) = NaiveDate::parse_and_remainder(rest, "%Y-%m-%d")
+            && let Some(expires_at) =
+                from_local((date + TimeDelta::days(1)).and_time(NaiveTime::MIN))
+        {
+            return (expires_at, note.trim());
+        }
+        if let (Some(time), note) = parse_optional_time(rest) {
+            return (next_daily(&now, time).with_timezone(&Utc), note);
+        }
+    }
+    if let Some(rest) = text.strip_prefix("на ").map(str::trim_start) {
+        let (hours, note) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if let Some(hours) = hours.strip_suffix('ч').and_then(|h| h.parse::<u32>().ok())
+            && hours > 0
+        {
+            let duration = TimeDelta::hours(hours.into()).min(EXPIRY_MAX_DURATION);
+            return (now.with_timezone(&Utc) + duration, note.trim());
+        }
+    }
+    (now.with_timezone(&Utc) + default, text)
+}
+
+/// Parses `до 23:00 заметка`, "до" is optional
+fn parse_open(text: &str) -> Option<(NaiveTime, &str)> {
+    let text = text.trim();
+    let text = text.strip_prefix("до ").unwrap_or(text).trim_start();
+    let (time, note) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    Some((NaiveTime::parse_from_str(time, "%H:%M").ok()?, note.trim()))
+}
+
+/// Text after `mention` if the text starts with it, mentions further in are just part of the text
+pub fn strip_leading_mention<'a>(text: &'a str, mention: &str) -> Option<&'a str> {
+    let rest = text.trim_start().strip_prefix(mention)?;
+    if !rest.is_empty() && !rest.starts_with(|c: char| c.is_whitespace() || c == ':') {
+        return None;
+    }
+    Some(rest.trim_start_matches(':').trim())
+}
+
+/// Parses `гость Имя: описание` that residents use for people without Telegram
+fn parse_guest(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("гость ")?;
+    let (name, purpose) = rest.split_once(':').unwrap_or((rest, ""));
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, purpose.trim()))
+}
+
+struct EventDraft<'a> {
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    capacity: Option<u32>,
+    title: &'a str,
+    description: &'a str,
+}
+
+/// Parses `YYYY-MM-DD HH:MM-HH:MM [мест:N] Название` with an optional description on the next lines
+fn parse_event(text: &str) -> Option<EventDraft<'_>> {
+    let (first_line, description) = text.split_once('\n').unwrap_or((text, ""));
+    let (day, rest) = NaiveDate::parse_and_remainder(first_line.trim(), "%Y-%m-%d").ok()?;
+    let (times, rest) = rest.trim().split_once(' ')?;
+    let (start, end) = times.split_once('-')?;
+    let starts_at = day.and_time(start.parse::<NaiveTime>().ok()?);
+    let mut ends_at = day.and_time(end.parse::<NaiveTime>().ok()?);
+    if ends_at <= starts_at {
+        // ends after midnight
+        ends_at += TimeDelta::days(1);
+    }
+    let rest = rest.trim();
+    let (capacity, title) = match rest.strip_prefix("мест:") {
+        Some(rest) => {
+            let (capacity, title) = rest.split_once(' ')?;
+            (
+                Some(capacity.parse().ok().filter(|c| *c > 0)?),
+                title.trim(),
+            )
+        }
+        None => (None, rest),
+    };
+    if title.is_empty() {
+        return None;
+    }
+    Some(EventDraft {
+        starts_at,
+        ends_at,
+        capacity,
+        title,
+        description: description.trim(),
+    })
+}
+
+fn parse_subscription(author: Uid, text: &str) -> Option<Subscription> {
+    let mut subscription = Subscription {
+        person: author,
+        notify_close: false,
+        quiet_hours: None,
+    };
+    for word in text.split_whitespace() {
+        if word == "закрытие" {
+            subscription.notify_close = true;
+            continue;
+        }
+        let (from, to) = word.split_once('-')?;
+        subscription.quiet_hours = Some(QuietHours {
+            from: NaiveTime::parse_from_str(from, "%H:%M").ok()?,
+            to: NaiveTime::parse_from_str(to, "%H:%M").ok()?,
+        });
+    }
+    Some(subscription)
+}
+
+const VISITS_HORIZON_DAYS: i64 = 185;
+const MESSAGE_LENGTH_LIMIT: usize = 4096;
+
+impl VisitsQuery {
+    fn range(&self) -> (NaiveDate, NaiveDate) {
+        let today = today();
+        match self.view {
+            VisitsView::Week => {
+                let from = today + TimeDelta::weeks(self.page as i64);
+                (from, from + TimeDelta::days(6))
+            }
+            VisitsView::Month => {
+                let first_day = today.with_day(1).expect("first day of month to exist")
+                    + Months::new(self.page);
+                let last_day = (first_day + Months::new(1))
+                    .pred_opt()
+                    .expect("last day of month to exist");
+                (first_day.max(today), last_day)
+            }
+        }
+    }
+
+    fn last_page(&self) -> u32 {
+        match self.view {
+            VisitsView::Week => (VISITS_HORIZON_DAYS / 7) as u32,
+            VisitsView::Month => (VISITS_HORIZON_DAYS / 30) as u32,
+        }
+    }
+
+    fn with_page(&self, view: VisitsView, page: u32) -> Self {
+        VisitsQuery {
+            view,
+            page,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+fn parse_visits_query(author: Uid, text: &str, replied_to: Option<Uid>) -> VisitsQuery {
+    let mut query = VisitsQuery {
+        view: VisitsView::Week,
+        page: 0,
+        filter: replied_to.map_or(VisitsFilter::All, VisitsFilter::Person),
+    };
+    let mut keyword = Vec::new();
+    for word in text.split_whitespace() {
+        match word {
+            "неделя" => query.view = VisitsView::Week,
+            "месяц" => query.view = VisitsView::Month,
+            "резиденты" => query.filter = VisitsFilter::Residents,
+            "мои" => query.filter = VisitsFilter::Person(author),
+            _ => keyword.push(word),
+        }
+    }
+    if !keyword.is_empty() {
+        query.filter = VisitsFilter::purpose(&keyword.join(" "));
+    }
+    query
+}
+
+/// Cuts `text` at a line boundary so it fits into a single Telegram message
+fn truncate_message(text: String) -> String {
+    // Telegram counts UTF-16 code units, markup is not counted, so this is conservative
+    if text.encode_utf16().count() <= MESSAGE_LENGTH_LIMIT {
+        return text;
+    }
+    let mut result = String::new();
+    let mut length = 0;
+    for line in text.lines() {
+        let line_length = line.encode_utf16().count() + 1;
+        if length + line_length > MESSAGE_LENGTH_LIMIT - 2 {
+            break;
+        }
+        result.push_str(line);
+        result.push('\n');
+        length += line_length;
+    }
+    result.push('…');
+    result
+}
+
+/// Keeps the first `max` lines, saying how many more there are
+fn cap_lines(lines: Vec<String>, max: usize) -> String {
+    let hidden = lines.len().saturating_sub(max);
+    let mut text = lines.into_iter().take(max).join("\n");
+    if hidden > 0 {
+        text.push_str(&format!("\n…и ещё {hidden}"));
+    }
+    text
+}
+
 fn format_close_date(date: NaiveDate) -> Option<&'static str> {
     let today = today();
     match (date - today).num_days() {
@@ -121,18 +436,128 @@ fn format_date(date: NaiveDate) -> String {
     base_date
 }
 
+/// Person a command acts on, set by residents acting on behalf of someone else
+struct CommandTarget {
+    person: Uid,
+    /// Resident acting on behalf of `person`
+    actor: Option<Uid>,
+    /// Command text without the guest name or mention
+    text: String,
+}
+
+enum OtherPerson<'a> {
+    Guest(&'a str),
+    User(Uid),
+}
+
+#[derive(Clone)]
 struct PersonDetails {
     resident: bool,
     display_name: String,
     link: String,
 }
 
-const LIVE_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+/// Host of each guest visit, keyed by the guest and the day
+type VisitHosts = HashMap<(Uid, NaiveDate), PersonDetails>;
+
+#[derive(Debug, Clone, Copy)]
+struct LiveStatusMessage {
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    message_id: MessageId,
+}
+
+/// What to restore when "↩️ Отменить" is pressed
+struct UndoAction {
+    person: Uid,
+    day: NaiveDate,
+    previous: Option<Visit>,
+}
+
+struct LiveStatus {
+    text: String,
+    open: bool,
+}
+
+// refreshes the "Обновлено" timestamp and picks up day rollover
+const LIVE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+// bursts of visit changes within this window result in a single edit
+const LIVE_UPDATE_COALESCE: Duration = Duration::from_secs(1);
+// start a new daily announcement once this many messages were posted after the current one
+const DAILY_ANNOUNCEMENT_SCROLL_LIMIT: i32 = 20;
+const DAILY_ANNOUNCEMENT_MAX_LINES: usize = 40;
+const OPEN_STATE_DEBOUNCE: Duration = Duration::from_secs(3 * 60);
+// how long the "↩️ Отменить" button stays around after a button press
+const UNDO_TIMEOUT: Duration = Duration::from_secs(30);
+// /me replies outside of DMs are deleted after this long to keep the chat clean
+const ME_AUTO_DELETE: Duration = Duration::from_secs(2 * 60);
+const DIGEST_MAX_PURPOSES: usize = 5;
+const EVENTS_LIST_DAYS: i64 = 60;
+const EVENT_REMINDER_BEFORE: TimeDelta = TimeDelta::hours(2);
+const EVENT_REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const OPEN_SESSION_REMINDER_BEFORE: TimeDelta = TimeDelta::minutes(15);
+const OPEN_SESSION_EXTEND_BY: TimeDelta = TimeDelta::hours(1);
+const OPEN_SESSION_MAX_DURATION: TimeDelta = TimeDelta::hours(12);
+const OPEN_SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const MOTD_DEFAULT_DURATION: TimeDelta = TimeDelta::hours(12);
+const MOTD_MAX_LEN: usize = 200;
+const NOTICE_DEFAULT_DURATION: TimeDelta = TimeDelta::days(7);
+const NOTICE_MAX_LEN: usize = 300;
+// for "на 3ч", dates are not limited
+const EXPIRY_MAX_DURATION: TimeDelta = TimeDelta::days(30);
+const CLOSURE_MAX_DAYS: i64 = 366;
+// items are tracked as bits of an u32
+const LOCKUP_MAX_ITEMS: usize = 32;
+// lists in the status are cut to this many lines, the pinned message should stay short
+const STATUS_SECTION_MAX_LINES: usize = 5;
+const AUDIT_LOG_PERIOD: TimeDelta = TimeDelta::days(7);
+const CLOSURES_LIST_DAYS: i64 = 90;
+const ME_HISTORY_DAYS: i64 = 30;
+const ME_MAX_PLANS: usize = 10;
+const ME_RECENT_VISITS: usize = 5;
+
+/// Tracks open/closed transitions, only reporting a new state after it has been stable for
+/// `OPEN_STATE_DEBOUNCE`, so quick checkout/checkin flaps don't produce notifications.
+#[derive(Default)]
+struct OpenStateTracker {
+    announced: Option<bool>,
+    changed_at: Option<Instant>,
+}
+
+impl OpenStateTracker {
+    fn update(&mut self, open: bool, now: Instant) -> Option<bool> {
+        let Some(announced) = self.announced else {
+            self.announced = Some(open);
+            return None;
+        };
+        if open == announced {
+            self.changed_at = None;
+            return None;
+        }
+        let changed_at = *self.changed_at.get_or_insert(now);
+        if now - changed_at < OPEN_STATE_DEBOUNCE {
+            return None;
+        }
+        self.announced = Some(open);
+        self.changed_at = None;
+        Some(open)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.changed_at.map(|t| t + OPEN_STATE_DEBOUNCE)
+    }
+}
 
 pub struct TelegramBot<B: Backend> {
     config: TelegramBotConfig,
     bot: Bot,
-    status_message_id: RwLock<Option<MessageId>>,
+    rate_limiter: RateLimiter,
+    status_messages: RwLock<Vec<LiveStatusMessage>>,
+    last_public_chat_message_id: AtomicI32,
+    undo_actions: Mutex<HashMap<u64, UndoAction>>,
+    next_undo_id: AtomicU64,
+    /// Usernames seen in the public chat, to resolve @mentions
+    known_usernames: RwLock<HashMap<String, UserId>>,
     backend: Weak<B>,
 }
 
@@ -142,7 +567,12 @@ impl<B: Backend> TelegramBot<B> {
         Ok(Arc::new(TelegramBot {
             config,
             bot,
-            status_message_id: RwLock::new(None),
+            rate_limiter: RateLimiter::new(),
+            status_messages: RwLock::new(Vec::new()),
+            last_public_chat_message_id: AtomicI32::new(0),
+            undo_actions: Mutex::new(HashMap::new()),
+            next_undo_id: AtomicU64::new(0),
+            known_usernames: RwLock::new(HashMap::new()),
             backend,
         }))
     }
@@ -153,34 +583,66 @@ impl<B: Backend> TelegramBot<B> {
     }
 
     async fn send_alert(&self) -> Result<()> {
-        self.bot
-            .send_message(self.config.alert_chat_id, "💥 Что-то пошло не так")
-            .await?;
+        self.request(
+            Some(self.config.alert_chat_id),
+            self.bot
+                .send_message(self.config.alert_chat_id, "💥 Что-то пошло не так"),
+        )
+        .await?;
         Ok(())
     }
 
+    /// Sends a user-facing request through the rate limiter
+    async fn request<R>(&self, chat: Option<ChatId>, request: R) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        self.rate_limiter.send(Priority::High, chat, request).await
+    }
+
+    /// Sends a request that can wait for user-facing ones through the rate limiter
+    async fn background_request<R>(
+        &self,
+        chat: Option<ChatId>,
+        request: R,
+    ) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        self.rate_limiter.send(Priority::Low, chat, request).await
+    }
+
+    pub fn queue_metrics(&self) -> QueueMetrics {
+        self.rate_limiter.metrics()
+    }
+
     pub async fn run(self: Arc<Self>) -> Result<()> {
         log::info!("Starting Telegram bot");
 
-        self.bot.set_my_commands(Command::bot_commands()).await?;
+        self.request(None, self.bot.set_my_commands(Command::bot_commands()))
+            .await?;
 
-        *self.status_message_id.write().unwrap() =
-            Self::load_status_message_id(self.backend().pool()).await?;
+        *self.status_messages.write().unwrap() =
+            Self::load_status_messages(self.backend().pool(), self.config.public_chat_id).await?;
 
         let self_clone_outer1 = self.clone();
 
         let handle_message = move |msg: Message, cmd: Command| {
             let self_clone = self_clone_outer1.clone();
             async move {
+                self_clone.track_public_chat_message(&msg);
                 let res = AssertUnwindSafe(self_clone.clone().handle_message(&msg, cmd))
                     .catch_unwind()
                     .await;
                 if matches!(res, Err(_) | Ok(Err(_))) {
                     self_clone.send_alert().await?;
                     self_clone
-                        .send_message_reply(
-                            &msg,
-                            "😬 Что-то пошло не так, но админ уже об этом знает",
+                        .request(
+                            Some(msg.chat.id),
+                            self_clone.send_message_reply(
+                                &msg,
+                                "😬 Что-то пошло не так, но админ уже об этом знает",
+                            ),
                         )
                         .await?;
                     if let Ok(e) = res {
@@ -199,14 +661,39 @@ impl<B: Backend> TelegramBot<B> {
                 let res = AssertUnwindSafe(self_clone.clone().handle_callback(&q))
                     .catch_unwind()
                     .await;
-                self_clone.bot.answer_callback_query(q.id.clone()).await?;
+                let mut answer = self_clone.bot.answer_callback_query(q.id.clone());
+                if let Ok(Ok(Some(ref text))) = res {
+                    answer = answer.text(text);
+                }
+                self_clone.request(None, answer).await?;
                 if matches!(res, Err(_) | Ok(Err(_))) {
                     self_clone.send_alert().await?;
                     self_clone
-                        .send_message_public_chat(
-                            "😬 Что-то пошло не так, но админ уже об этом знает",
+                        .request(
+                            Some(self_clone.config.public_chat_id),
+                            self_clone.send_message_public_chat(
+                                "😬 Что-то пошло не так, но админ уже об этом знает",
+                            ),
                         )
                         .await?;
+                    if let Ok(Err(e)) = res {
+                        return Err(e);
+                    }
+                }
+                Ok(())
+            }
+        };
+
+        let self_clone_outer3 = self.clone();
+
+        let handle_edited_message = move |msg: Message, cmd: Command| {
+            let self_clone = self_clone_outer3.clone();
+            async move {
+                let res = AssertUnwindSafe(self_clone.handle_edited_message(&msg, cmd))
+                    .catch_unwind()
+                    .await;
+                if matches!(res, Err(_) | Ok(Err(_))) {
+                    self_clone.send_alert().await?;
                     if let Ok(e) = res {
                         return e;
                     }
@@ -215,15 +702,31 @@ impl<B: Backend> TelegramBot<B> {
             }
         };
 
+        let self_clone_outer4 = self.clone();
+
+        let track_message = move |msg: Message| {
+            self_clone_outer4.track_public_chat_message(&msg);
+            async { Ok(()) }
+        };
+
         let handler = dptree::entry()
             .branch(
                 Update::filter_message()
                     .filter_command::<Command>()
                     .endpoint(handle_message),
             )
-            .branch(Update::filter_callback_query().endpoint(handle_callback));
+            .branch(
+                Update::filter_edited_message()
+                    .filter_command::<Command>()
+                    .endpoint(handle_edited_message),
+            )
+            .branch(Update::filter_callback_query().endpoint(handle_callback))
+            .branch(Update::filter_message().endpoint(track_message));
 
         let live_update_ct = self.clone().spawn_update_live_task().await;
+        let weekly_digest_ct = self.spawn_weekly_digest_task();
+        let event_reminders_ct = self.spawn_event_reminders_task();
+        let open_sessions_ct = self.spawn_open_sessions_task();
 
         Dispatcher::builder(self.bot.clone(), handler)
             .enable_ctrlc_handler()
@@ -232,6 +735,9 @@ impl<B: Backend> TelegramBot<B> {
             .await;
 
         live_update_ct.cancel();
+        weekly_digest_ct.cancel();
+        event_reminders_ct.cancel();
+        open_sessions_ct.cancel();
 
         Ok(())
     }
@@ -240,12 +746,18 @@ impl<B: Backend> TelegramBot<B> {
         InlineKeyboardMarkup {
             inline_keyboard: vec![
                 vec![
-                    InlineKeyboardButton::callback("👷 Я зашёл", "/checkin"),
-                    InlineKeyboardButton::callback("🌆 Я ушёл", "/checkout"),
+                    InlineKeyboardButton::callback("👷 Я зашёл", CallbackData::CheckIn.encode()),
+                    InlineKeyboardButton::callback("🌆 Я ушёл", CallbackData::CheckOut.encode()),
                 ],
                 vec![
-                    InlineKeyboardButton::callback("🚋 Зайду сегодня", "/planvisit"),
-                    InlineKeyboardButton::callback("🤔 Передумал", "/unplanvisit"),
+                    InlineKeyboardButton::callback(
+                        "🚋 Зайду сегодня",
+                        CallbackData::PlanVisit(None).encode(),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "🤔 Передумал",
+                        CallbackData::UnplanVisit(None).encode(),
+                    ),
                 ],
             ],
         }
@@ -256,14 +768,33 @@ impl<B: Backend> TelegramBot<B> {
         let result = cancellation_token.clone();
         let self_clone = self.clone();
 
+        let mut changes = self.backend().subscribe_changes();
+
         tokio::task::spawn(async move {
-            let mut interval = tokio::time::interval(LIVE_UPDATE_INTERVAL);
+            let mut interval = tokio::time::interval(LIVE_REFRESH_INTERVAL);
 
-            let mut last_live_status = None;
+            let mut last_live_status: Option<LiveStatus> = None;
+            let mut open_state = OpenStateTracker::default();
 
             loop {
+                let open_state_deadline = open_state.deadline();
+                let force_refresh;
                 tokio::select! {
-                    _ = interval.tick() => {}
+                    _ = interval.tick() => { force_refresh = true; }
+                    res = changes.changed() => {
+                        if res.is_err() {
+                            break;
+                        }
+                        tokio::time::sleep(LIVE_UPDATE_COALESCE).await;
+                        changes.mark_unchanged();
+                        force_refresh = false;
+                    }
+                    _ = async {
+                        match open_state_deadline {
+                            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                            None => futures::future::pending().await,
+                        }
+                    } => { force_refresh = false; }
                     _ = cancellation_token.cancelled() => { break }
                 };
                 log::trace!("Updating status message");
@@ -274,13 +805,19 @@ impl<B: Backend> TelegramBot<B> {
                         continue;
                     }
                 };
-                if last_live_status.is_none_or(|ref v| v != &new_live_status)
+                if (force_refresh
+                    || last_live_status
+                        .as_ref()
+                        .is_none_or(|v| v.text != new_live_status.text))
                     && let Err(e) = self_clone
-                        .update_live_status_message(&new_live_status)
+                        .update_live_status_message(&new_live_status.text, force_refresh)
                         .await
                 {
                     log::error!("Error updating status message: {:?}", e);
                 }
+                if let Some(open) = open_state.update(new_live_status.open, Instant::now()) {
+                    self_clone.announce_open_state(open).await;
+                }
                 last_live_status = Some(new_live_status);
             }
         });
@@ -288,497 +825,3429 @@ impl<B: Backend> TelegramBot<B> {
         result
     }
 
-    async fn handle_message(self: Arc<Self>, msg: &Message, cmd: Command) -> Result<()> {
-        match cmd {
-            Command::PostLive => self.handle_post_live(msg).await,
-            Command::Status => self.handle_status(msg).await,
-            Command::GetVisits => self.handle_get_visits(msg).await,
-            Command::PlanVisit => self.handle_plan_visit(msg).await,
-            Command::UnplanVisit => self.handle_unplan_visit(msg).await,
-            Command::CheckIn => self.handle_check_in(msg).await,
-            Command::CheckOut => self.handle_check_out(msg).await,
-            Command::Close => self.handle_close(msg).await,
-            Command::LiveStatus => self.handle_live_status(msg).await,
-            Command::UnLiveStatus => self.handle_unlive_status(msg).await,
-        }
-    }
+    fn spawn_weekly_digest_task(self: &Arc<Self>) -> CancellationToken {
+        let cancellation_token = CancellationToken::new();
+        let Some(digest_config) = self.config.weekly_digest.clone() else {
+            return cancellation_token;
+        };
+        let result = cancellation_token.clone();
+        let self_clone = self.clone();
 
-    async fn is_resident(&self, id: UserId) -> Result<bool> {
-        Ok(self
-            .bot
-            .get_chat_member(self.config.private_chat_id, id)
-            .await?
-            .is_present())
+        tokio::task::spawn(async move {
+            loop {
+                let now = crate::utils::now();
+                let at = next_weekly(&now, digest_config.weekday, digest_config.time);
+                tokio::select! {
+                    _ = tokio::time::sleep((at - now).to_std().unwrap_or_default()) => {}
+                    _ = cancellation_token.cancelled() => { break }
+                }
+                if let Err(e) = self_clone.post_weekly_digest().await {
+                    log::error!("Error posting weekly digest: {:?}", e);
+                }
+            }
+        });
+
+        result
     }
 
-    async fn fetch_person_details(&self, user: Uid) -> Result<PersonDetails> {
-        let user_id = user.0;
-        let chat_member = self
-            .bot
-            .get_chat_member(self.config.public_chat_id, user_id)
+    async fn format_weekly_digest(&self) -> Result<String> {
+        let today = today();
+        let (from, to) = (today - TimeDelta::days(7), today - TimeDelta::days(1));
+        let stats = self.backend().get_stats(from, to).await?;
+        let mut text = format!(
+            "📰 Итоги недели\n\n{}",
+            self.format_stats(StatsPeriod::Week, &stats).await?
+        );
+
+        let last_week_visits = self.backend().get_visits(from, to).await?;
+        let purposes = last_week_visits
+            .iter()
+            .filter(|v| v.status != VisitStatus::Planned && !v.purpose.is_empty())
+            .map(|v| v.purpose.as_str())
+            .counts()
+            .into_iter()
+            .sorted_by_key(|(purpose, count)| (std::cmp::Reverse(*count), *purpose))
+            .take(DIGEST_MAX_PURPOSES)
+            .map(|(purpose, _)| format!("• \"{purpose}\""))
+            .join("\n");
+        if !purposes.is_empty() {
+            text.push_str("\n\n💡 Чем занимались:\n");
+            text.push_str(&purposes);
+        }
+
+        let week_visits = self
+            .backend()
+            .get_visits(today, today + TimeDelta::days(6))
             .await?;
-        let resident = self.is_resident(user_id).await?;
-        let display_name = if let Some(ref username) = chat_member.user.username {
-            username.clone()
+        let details = self
+            .fetch_persons_details(week_visits.iter().map(|v| v.person))
+            .await?;
+        let hosts = self
+            .get_visit_hosts(today, today + TimeDelta::days(6))
+            .await?;
+        let formatted_week_visits = self.format_visits(week_visits, &details, &hosts);
+        if formatted_week_visits.is_empty() {
+            text.push_str("\n\n🗓️ Планов на неделю пока нет, /planvisit чтобы запланировать");
         } else {
-            chat_member.user.full_name()
-        };
-        let link = chat_member.user.preferably_tme_url().to_string();
-        Ok(PersonDetails {
-            resident,
-            display_name,
-            link,
-        })
-    }
+            text.push_str("\n\n🗓️ Планы на неделю:\n\n");
+            text.push_str(&formatted_week_visits);
+        }
 
-    async fn fetch_persons_details(
-        &self,
-        persons: impl IntoIterator<Item = Uid>,
-    ) -> Result<HashMap<Uid, PersonDetails>> {
-        Ok(futures::future::try_join_all(
-            persons.into_iter().unique().map(async |user| -> Result<_> {
-                Ok((user, self.fetch_person_details(user).await?))
-            }),
-        )
-        .await?
-        .into_iter()
-        .collect::<HashMap<_, _>>())
+        Ok(truncate_message(text))
     }
 
-    async fn check_is_public_chat_msg(&self, msg: &Message) -> Result<Option<ChatId>> {
-        let chat_id = msg.chat.id;
-        if chat_id != self.config.public_chat_id {
-            log::debug!("check_is_public_chat_msg failed: {:?}", msg);
-            self.send_message_reply(msg, "❌ Нужно написать в публичный чат спейса")
+    async fn post_weekly_digest(&self) -> Result<()> {
+        let Some(digest_config) = &self.config.weekly_digest else {
+            return Ok(());
+        };
+        let text = self.format_weekly_digest().await?;
+
+        if digest_config.to_channel {
+            let channel_id = self.config.public_channel_id;
+            self.background_request(Some(channel_id), self.send_message_to(channel_id, &text))
                 .await?;
-            return Ok(None);
         }
-        Ok(Some(chat_id))
+        if digest_config.to_chat {
+            self.background_request(
+                Some(self.config.public_chat_id),
+                self.send_message_public_chat(&text),
+            )
+            .await?;
+        }
+
+        Ok(())
     }
 
-    async fn check_author_is_resident(&self, msg: &Message) -> Result<bool> {
-        if !self
-            .is_resident(msg.from.as_ref().expect("message to have author").id)
-            .await?
-        {
-            log::debug!("check_author_is_resident failed: {:?}", msg);
-            self.send_message_reply(msg, "❌ Нужно быть резидентом")
-                .await?;
-            return Ok(false);
+    fn spawn_event_reminders_task(self: &Arc<Self>) -> CancellationToken {
+        let cancellation_token = CancellationToken::new();
+        let result = cancellation_token.clone();
+        let self_clone = self.clone();
+
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(EVENT_REMINDER_CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = cancellation_token.cancelled() => { break }
+                }
+                if let Err(e) = self_clone.send_event_reminders().await {
+                    log::error!("Error sending event reminders: {:?}", e);
+                }
+            }
+        });
+
+        result
+    }
+
+    fn spawn_open_sessions_task(self: &Arc<Self>) -> CancellationToken {
+        let cancellation_token = CancellationToken::new();
+        let result = cancellation_token.clone();
+        let self_clone = self.clone();
+
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(OPEN_SESSION_CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = cancellation_token.cancelled() => { break }
+                }
+                if let Err(e) = self_clone.check_open_sessions().await {
+                    log::error!("Error checking open sessions: {:?}", e);
+                }
+            }
+        });
+
+        result
+    }
+
+    /// Closes sessions past their time and reminds openers of the ones closing soon
+    async fn check_open_sessions(&self) -> Result<()> {
+        for session in self.backend().close_expired_open_sessions().await? {
+            self.notify_persons(
+                &[session.opener],
+                "🔒 Время вышло, отметил, что спейс больше не открыт для гостей",
+            )
+            .await;
+        }
+
+        let sessions = self
+            .backend()
+            .get_due_open_session_reminders(Utc::now() + OPEN_SESSION_REMINDER_BEFORE)
+            .await?;
+        for session in sessions {
+            // Marked first, a failed reminder is better than a repeated one
+            self.backend()
+                .mark_open_session_reminded(session.id)
+                .await?;
+
+            let chat_id = ChatId::from(session.opener.0);
+            let markup = InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback(
+                    "➕ Ещё час",
+                    CallbackData::OpenSession {
+                        session: session.id,
+                        extend: true,
+                    }
+                    .encode(),
+                ),
+                InlineKeyboardButton::callback(
+                    "🔒 Закрыть сейчас",
+                    CallbackData::OpenSession {
+                        session: session.id,
+                        extend: false,
+                    }
+                    .encode(),
+                ),
+            ]]);
+            let text = format!(
+                "⏰ Спейс открыт до {}, скоро закрою. Если ещё сидите — продли",
+                to_local(session.closes_at).format("%H:%M")
+            );
+            if let Err(e) = self
+                .background_request(
+                    Some(chat_id),
+                    self.send_message_to(chat_id, text).reply_markup(markup),
+                )
+                .await
+            {
+                log::warn!("Failed to remind {:?} about closing: {e}", session.opener);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_open_session_callback(
+        &self,
+        q: &CallbackQuery,
+        id: i64,
+        extend: bool,
+    ) -> Result<String> {
+        let now = Utc::now();
+        let Some(session) = self
+            .backend()
+            .get_open_session()
+            .await?
+            .filter(|s| s.id == id && s.is_open(now))
+        else {
+            return Ok("Спейс уже закрыт".to_owned());
+        };
+
+        let toast = if extend {
+            let closes_at = session.closes_at + OPEN_SESSION_EXTEND_BY;
+            self.backend()
+                .extend_open_session(session.id, closes_at)
+                .await?;
+            format!("🟢 Продлил до {}", to_local(closes_at).format("%H:%M"))
+        } else {
+            self.backend().close_open_session(session.id).await?;
+            "🔒 Закрыл".to_owned()
+        };
+
+        if let Some(msg) = q.regular_message() {
+            self.request(
+                Some(msg.chat.id),
+                self.bot.edit_message_text(msg.chat.id, msg.id, &toast),
+            )
+            .await?;
+        }
+
+        Ok(toast)
+    }
+
+    async fn send_event_reminders(&self) -> Result<()> {
+        let now = Utc::now();
+        let events = self
+            .backend()
+            .get_due_event_reminders(now, now + EVENT_REMINDER_BEFORE)
+            .await?;
+        for event in events {
+            // Marked first, a failed reminder is better than a repeated one
+            self.backend().mark_event_reminded(event.id).await?;
+
+            let text = format!(
+                "⏰ Скоро начнётся <b>{}</b>: {}",
+                teloxide::utils::html::escape(&event.title),
+                Self::format_event_time(&event)
+            );
+            self.background_request(
+                Some(self.config.public_chat_id),
+                self.send_message_public_chat(&text),
+            )
+            .await?;
+
+            let attendees = self.backend().get_event_attendees(event.id).await?;
+            let (going, _) = event.split_attendees(&attendees);
+            self.notify_persons(going, &text).await;
+        }
+        Ok(())
+    }
+
+    /// Sends a DM to each person, skipping guests and people who haven't started the bot
+    async fn notify_persons(&self, persons: &[Uid], text: &str) {
+        for person in persons.iter().filter(|p| !p.is_guest()) {
+            let chat_id = ChatId::from(person.0);
+            if let Err(e) = self
+                .background_request(Some(chat_id), self.send_message_to(chat_id, text))
+                .await
+            {
+                log::warn!("Failed to notify {:?}: {e}", person);
+            }
+        }
+    }
+
+    fn format_event_time(event: &Event) -> String {
+        let starts_at = to_local(event.starts_at);
+        let ends_at = to_local(event.ends_at);
+        format!(
+            "{}, {}–{}",
+            format_date(starts_at.date_naive()),
+            starts_at.format("%H:%M"),
+            ends_at.format("%H:%M")
+        )
+    }
+
+    fn format_calendar_time(event: &CalendarEvent) -> String {
+        match (event.start, event.end) {
+            (CalendarTime::Date(start), CalendarTime::Date(end)) => {
+                // DTEND of all-day events is exclusive
+                let last_day = end - TimeDelta::days(1);
+                if last_day > start {
+                    format!("{} — {}", format_date(start), format_date(last_day))
+                } else {
+                    format_date(start)
+                }
+            }
+            (start, end) => {
+                let starts_at = to_local(start.to_utc());
+                let ends_at = to_local(end.to_utc());
+                format!(
+                    "{}, {}–{}",
+                    format_date(starts_at.date_naive()),
+                    starts_at.format("%H:%M"),
+                    ends_at.format("%H:%M")
+                )
+            }
+        }
+    }
+
+    /// One line per imported calendar event
+    fn format_calendar_events(events: &[CalendarEvent]) -> String {
+        events
+            .iter()
+            .map(|e| {
+                format!(
+                    "• 📆 {} <b>{}</b>",
+                    Self::format_calendar_time(e),
+                    teloxide::utils::html::escape(&e.summary)
+                )
+            })
+            .join("\n")
+    }
+
+    fn format_attendance(event: &Event, attendees: &[Uid]) -> String {
+        let (going, waitlist) = event.split_attendees(attendees);
+        let mut text = match event.capacity {
+            Some(capacity) => format!("{}/{capacity}", going.len()),
+            None => going.len().to_string(),
+        };
+        if !waitlist.is_empty() {
+            text.push_str(&format!(", ожидают {}", waitlist.len()));
+        }
+        text
+    }
+
+    /// One line per event
+    async fn format_events(&self, events: &[Event]) -> Result<String> {
+        let mut lines = Vec::new();
+        for event in events {
+            let attendees = self.backend().get_event_attendees(event.id).await?;
+            lines.push(format!(
+                "• #{} {} <b>{}</b> ({})",
+                event.id,
+                Self::format_event_time(event),
+                teloxide::utils::html::escape(&event.title),
+                Self::format_attendance(event, &attendees)
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    async fn render_event(&self, event: &Event) -> Result<(String, InlineKeyboardMarkup)> {
+        let attendees = self.backend().get_event_attendees(event.id).await?;
+        let details = self
+            .fetch_persons_details(attendees.iter().copied().chain([event.organizer]))
+            .await?;
+        let (going, waitlist) = event.split_attendees(&attendees);
+        let format_persons = |persons: &[Uid]| {
+            persons
+                .iter()
+                .map(|p| self.format_person_link(&details[p]))
+                .join(", ")
+        };
+
+        let mut text = format!(
+            "🎪 <b>{}</b>{}\n🗓️ {}\n👤 Организатор: {}",
+            teloxide::utils::html::escape(&event.title),
+            if event.cancelled {
+                " (отменено)"
+            } else {
+                ""
+            },
+            Self::format_event_time(event),
+            self.format_person_link(&details[&event.organizer]),
+        );
+        if !event.description.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&teloxide::utils::html::escape(&event.description));
+        }
+        text.push_str(&format!(
+            "\n\n✋ Идут ({}){}",
+            Self::format_attendance(event, &attendees),
+            if going.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", format_persons(going))
+            }
+        ));
+        if !waitlist.is_empty() {
+            text.push_str(&format!("\n⏳ Лист ожидания: {}", format_persons(waitlist)));
+        }
+
+        let markup = if event.cancelled || event.starts_at < Utc::now() {
+            InlineKeyboardMarkup::default()
+        } else {
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![vec![
+                    InlineKeyboardButton::callback(
+                        "✋ Пойду",
+                        CallbackData::Rsvp {
+                            event: event.id,
+                            going: true,
+                        }
+                        .encode(),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "🙅 Не пойду",
+                        CallbackData::Rsvp {
+                            event: event.id,
+                            going: false,
+                        }
+                        .encode(),
+                    ),
+                ]],
+            }
+        };
+
+        Ok((truncate_message(text), markup))
+    }
+
+    fn format_closure_reason(closure: &Closure) -> String {
+        if closure.reason.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", teloxide::utils::html::escape(&closure.reason))
+        }
+    }
+
+    /// One line per run of consecutive days with the same reason
+    fn format_closures(closures: &[Closure]) -> String {
+        closures
+            .chunk_by(|a, b| b.day == a.day + TimeDelta::days(1) && a.reason == b.reason)
+            .map(|run| {
+                let (first, last) = (&run[0], &run[run.len() - 1]);
+                let days = if first.day == last.day {
+                    format_date(first.day)
+                } else {
+                    format!("{} — {}", format_date(first.day), format_date(last.day))
+                };
+                format!("• {days}{}", Self::format_closure_reason(first))
+            })
+            .join("\n")
+    }
+
+    async fn get_closure(&self, day: NaiveDate) -> Result<Option<Closure>> {
+        Ok(self.backend().get_closures(day, day).await?.pop())
+    }
+
+    /// Shown wherever somebody gets a visit planned, `None` if the space is open that day
+    async fn closure_warning(&self, day: NaiveDate) -> Result<Option<String>> {
+        Ok(self.get_closure(day).await?.map(|closure| {
+            format!(
+                "🚧 {} спейс закрыт{}",
+                format_date(day),
+                Self::format_closure_reason(&closure)
+            )
+        }))
+    }
+
+    async fn handle_closure(&self, msg: &Message) -> Result<()> {
+        let text = Self::message_text(msg);
+        let (subcommand, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        match subcommand {
+            "add" => self.handle_closure_add(msg, args).await,
+            "remove" => self.handle_closure_remove(msg, args).await,
+            "" | "list" => self.handle_closure_list(msg).await,
+            _ => {
+                self.request(
+                    Some(msg.chat.id),
+                    self.send_message_reply(
+                        msg,
+                        "❌ Не понял, смотри описание команды /closure в меню",
+                    ),
+                )
+                .await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_closure_add(&self, msg: &Message, args: &str) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+        let Some((from, to, reason)) = parse_day_range(args) else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "❌ Формат: /closure add YYYY-MM-DD [YYYY-MM-DD] причина, не больше {CLOSURE_MAX_DAYS} дней"
+                    ),
+                ),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let added = self
+            .backend()
+            .add_closures(from, to, reason.to_owned(), Self::message_author(msg))
+            .await?;
+
+        // people who planned before the day got closed, the rest were warned when planning
+        let affected = self
+            .backend()
+            .get_visits(from, to)
+            .await?
+            .into_iter()
+            .filter(|v| {
+                v.status == VisitStatus::Planned && added.contains(&v.day) && !v.person.is_guest()
+            })
+            .collect_vec();
+        let reason = if reason.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", teloxide::utils::html::escape(reason))
+        };
+        for visit in &affected {
+            let chat_id = ChatId::from(visit.person.0);
+            let markup = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+                "🤔 Отменить визит",
+                CallbackData::UnplanVisit(Some(visit.day)).encode(),
+            )]]);
+            if let Err(e) = self
+                .background_request(
+                    Some(chat_id),
+                    self.send_message_to(
+                        chat_id,
+                        format!(
+                            "🚧 {} спейс будет закрыт{reason}\n\nТы планировал зайти в этот день, лучше перенести визит",
+                            format_date(visit.day)
+                        ),
+                    )
+                    .reply_markup(markup),
+                )
+                .await
+            {
+                log::warn!("Failed to notify {:?} about closure: {e}", visit.person);
+            }
+        }
+
+        let mut text = format!("🚧 Отметил, что спейс закрыт{reason}");
+        if !affected.is_empty() {
+            text.push_str(&format!(
+                "\n\n📨 Написал тем, кто уже планировал зайти: {}",
+                affected.len()
+            ));
+        }
+        self.request(Some(msg.chat.id), self.send_message_reply(msg, text))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn handle_closure_remove(&self, msg: &Message, args: &str) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+        let Some((from, to, _)) = parse_day_range(args) else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Формат: /closure remove YYYY-MM-DD [YYYY-MM-DD]"),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let text = if self.backend().remove_closures(from, to).await? > 0 {
+            "🟢 Убрал закрытие"
+        } else {
+            "В эти дни спейс и так не был закрыт"
+        };
+        self.request(Some(msg.chat.id), self.send_message_reply(msg, text))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn handle_closure_list(&self, msg: &Message) -> Result<()> {
+        let today = today();
+        let closures = self
+            .backend()
+            .get_closures(today, today + TimeDelta::days(CLOSURES_LIST_DAYS))
+            .await?;
+        let text = if closures.is_empty() {
+            "🟢 Закрытых дней не запланировано".to_owned()
+        } else {
+            format!("🚧 Спейс закрыт:\n{}", Self::format_closures(&closures))
+        };
+        self.request(Some(msg.chat.id), self.send_message_reply(msg, text))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn handle_event(&self, msg: &Message) -> Result<()> {
+        let text = Self::message_text(msg);
+        let (subcommand, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        match subcommand {
+            "create" => self.handle_event_create(msg, args).await,
+            "cancel" => self.handle_event_cancel(msg, args).await,
+            "" | "list" => self.handle_event_list(msg).await,
+            id => match id.parse() {
+                Ok(id) => self.handle_event_show(msg, id).await,
+                Err(_) => {
+                    self.request(
+                        Some(msg.chat.id),
+                        self.send_message_reply(
+                            msg,
+                            "❌ Не понял, смотри описание команды /event в меню",
+                        ),
+                    )
+                    .await?;
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    async fn handle_event_create(&self, msg: &Message, args: &str) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+
+        let Some(draft) = parse_event(args) else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    "❌ Формат: /event create YYYY-MM-DD HH:MM-HH:MM [мест:N] Название, описание с новой строки",
+                ),
+            )
+            .await?;
+            return Ok(());
+        };
+        let (Some(starts_at), Some(ends_at)) =
+            (from_local(draft.starts_at), from_local(draft.ends_at))
+        else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Такого времени не бывает из-за перевода часов"),
+            )
+            .await?;
+            return Ok(());
+        };
+        if starts_at < Utc::now() {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Событие должно быть в будущем"),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let id = self
+            .backend()
+            .create_event(NewEvent {
+                title: draft.title.to_owned(),
+                description: draft.description.to_owned(),
+                starts_at,
+                ends_at,
+                organizer: Self::message_author(msg),
+                capacity: draft.capacity,
+            })
+            .await?;
+
+        self.handle_event_show(msg, id).await
+    }
+
+    async fn handle_event_show(&self, msg: &Message, id: i64) -> Result<()> {
+        let Some(event) = self.backend().get_event(id).await? else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "🤷 Нет такого события"),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let (text, markup) = self.render_event(&event).await?;
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(msg, text).reply_markup(markup),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_event_list(&self, msg: &Message) -> Result<()> {
+        let now = Utc::now();
+        let events = self
+            .backend()
+            .get_events(now, now + TimeDelta::days(EVENTS_LIST_DAYS))
+            .await?;
+        let text = if events.is_empty() {
+            "🎪 Ближайших событий нет".to_owned()
+        } else {
+            format!(
+                "🎪 Ближайшие события:\n\n{}\n\n/event N — подробности и запись",
+                self.format_events(&events).await?
+            )
+        };
+
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(msg, truncate_message(text)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_event_cancel(&self, msg: &Message, args: &str) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+
+        let cancelled = match args.trim().parse() {
+            Ok(id) => self
+                .backend()
+                .cancel_event(id)
+                .await?
+                .map(|attendees| (id, attendees)),
+            Err(_) => None,
+        };
+        let Some((id, attendees)) = cancelled else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "🤷 Нет такого события, или оно уже отменено"),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        if let Some(event) = self.backend().get_event(id).await? {
+            let text = format!(
+                "😢 Событие <b>{}</b> ({}) отменено",
+                teloxide::utils::html::escape(&event.title),
+                Self::format_event_time(&event)
+            );
+            self.notify_persons(&attendees, &text).await;
+        }
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_rsvp_callback(
+        &self,
+        q: &CallbackQuery,
+        id: i64,
+        going: bool,
+    ) -> Result<String> {
+        let person = Uid(q.from.id);
+        let Some(event) = self.backend().get_event(id).await? else {
+            return Ok("🤷 Нет такого события".to_owned());
+        };
+        if event.cancelled {
+            return Ok("😢 Событие отменено".to_owned());
+        }
+        if event.starts_at < Utc::now() {
+            return Ok("Событие уже началось".to_owned());
+        }
+
+        let event_day = day_of(event.starts_at);
+        let toast = if going {
+            match self.backend().rsvp(&event, person).await? {
+                Some(status) => {
+                    let toast = match status {
+                        RsvpStatus::Going => "✋ Записал тебя и запланировал визит",
+                        RsvpStatus::Waitlisted => "⏳ Мест нет, записал в лист ожидания",
+                    };
+                    match self.closure_warning(event_day).await? {
+                        Some(warning) => format!("{toast}\n\n{warning}"),
+                        None => toast.to_owned(),
+                    }
+                }
+                None => "Ты уже записан".to_owned(),
+            }
+        } else {
+            let unrsvp = self.backend().unrsvp(&event, person).await?;
+            if let Some(promoted) = unrsvp.promoted {
+                let mut text = format!(
+                    "🎉 Освободилось место на <b>{}</b> ({}), ты записан",
+                    teloxide::utils::html::escape(&event.title),
+                    Self::format_event_time(&event)
+                );
+                if let Some(warning) = self.closure_warning(event_day).await? {
+                    text.push_str(&format!("\n\n{warning}"));
+                }
+                self.notify_persons(&[promoted], &text).await;
+            }
+            if unrsvp.removed {
+                "🙅 Вычеркнул тебя".to_owned()
+            } else {
+                "Тебя и не было в списке".to_owned()
+            }
+        };
+
+        if let Some(msg) = q.regular_message() {
+            let (text, markup) = self.render_event(&event).await?;
+            self.request(
+                Some(msg.chat.id),
+                self.bot
+                    .edit_message_text(msg.chat.id, msg.id, text)
+                    .parse_mode(ParseMode::Html)
+                    .disable_link_preview(true)
+                    .reply_markup(markup),
+            )
+            .await?;
+        }
+
+        Ok(toast)
+    }
+
+    async fn handle_message(self: Arc<Self>, msg: &Message, cmd: Command) -> Result<()> {
+        match cmd {
+            Command::PostLive => self.handle_post_live(msg).await,
+            Command::Status => self.handle_status(msg).await,
+            Command::GetVisits => self.handle_get_visits(msg).await,
+            Command::PlanVisit => self.handle_plan_visit(msg).await,
+            Command::UnplanVisit => self.handle_unplan_visit(msg).await,
+            Command::CheckIn => self.handle_check_in(msg).await,
+            Command::Purpose => self.handle_purpose(msg).await,
+            Command::Me => self.handle_me(msg).await,
+            Command::Calendar => self.handle_calendar(msg).await,
+            Command::Closure => self.handle_closure(msg).await,
+            Command::Event => self.handle_event(msg).await,
+            Command::Stats => self.handle_stats(msg).await,
+            Command::CheckOut => self.handle_check_out(msg).await,
+            Command::AskOpen => self.handle_ask_open(msg).await,
+            Command::Open => self.handle_open(msg).await,
+            Command::Motd => self.handle_motd(msg).await,
+            Command::Notice => self.handle_notice(msg).await,
+            Command::Notices => self.handle_notices(msg).await,
+            Command::Close => self.handle_close(msg).await,
+            Command::Audit => self.handle_audit(msg).await,
+            Command::LiveStatus => self.handle_live_status(msg).await,
+            Command::UnLiveStatus => self.handle_unlive_status(msg).await,
+            Command::Subscribe => self.handle_subscribe(msg).await,
+            Command::Unsubscribe => self.handle_unsubscribe(msg).await,
+        }
+    }
+
+    async fn is_resident(&self, id: UserId) -> Result<bool> {
+        Ok(self
+            .request(
+                None,
+                self.bot.get_chat_member(self.config.private_chat_id, id),
+            )
+            .await?
+            .is_present())
+    }
+
+    /// Which of `persons` are residents, guests without Telegram never are
+    pub async fn residents(&self, persons: impl IntoIterator<Item = Uid>) -> Result<HashSet<Uid>> {
+        Ok(futures::future::try_join_all(
+            persons.into_iter().unique().filter(|p| !p.is_guest()).map(
+                async |person| -> Result<_> { Ok((person, self.is_resident(person.0).await?)) },
+            ),
+        )
+        .await?
+        .into_iter()
+        .filter(|(_, resident)| *resident)
+        .map(|(person, _)| person)
+        .collect())
+    }
+
+    async fn fetch_person_details(&self, user: Uid) -> Result<PersonDetails> {
+        if user.is_guest() {
+            let name = self.backend().guest_name(user).await?;
+            return Ok(PersonDetails {
+                resident: false,
+                display_name: teloxide::utils::html::escape(
+                    name.as_deref().unwrap_or("неизвестный гость"),
+                ),
+                link: String::new(),
+            });
+        }
+        let user_id = user.0;
+        let chat_member = self
+            .request(
+                None,
+                self.bot
+                    .get_chat_member(self.config.public_chat_id, user_id),
+            )
+            .await?;
+        let resident = self.is_resident(user_id).await?;
+        let display_name = if let Some(ref username) = chat_member.user.username {
+            username.clone()
+        } else {
+            chat_member.user.full_name()
+        };
+        let link = chat_member.user.preferably_tme_url().to_string();
+        Ok(PersonDetails {
+            resident,
+            display_name,
+            link,
+        })
+    }
+
+    async fn fetch_persons_details(
+        &self,
+        persons: impl IntoIterator<Item = Uid>,
+    ) -> Result<HashMap<Uid, PersonDetails>> {
+        Ok(futures::future::try_join_all(
+            persons.into_iter().unique().map(async |user| -> Result<_> {
+                Ok((user, self.fetch_person_details(user).await?))
+            }),
+        )
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>())
+    }
+
+    async fn check_is_public_chat_msg(&self, msg: &Message) -> Result<Option<ChatId>> {
+        let chat_id = msg.chat.id;
+        if chat_id != self.config.public_chat_id {
+            log::debug!("check_is_public_chat_msg failed: {:?}", msg);
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Нужно написать в публичный чат спейса"),
+            )
+            .await?;
+            return Ok(None);
+        }
+        Ok(Some(chat_id))
+    }
+
+    async fn check_author_is_resident(&self, msg: &Message) -> Result<bool> {
+        if !self
+            .is_resident(msg.from.as_ref().expect("message to have author").id)
+            .await?
+        {
+            log::debug!("check_author_is_resident failed: {:?}", msg);
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Нужно быть резидентом"),
+            )
+            .await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    async fn handle_post_live(&self, msg: &Message) -> Result<()> {
+        let Some(chat_id) = self.check_is_public_chat_msg(msg).await? else {
+            return Ok(());
+        };
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+
+        let Some(original_message) = msg.reply_to_message() else {
+            log::debug!("message is not a reply: {:?}", msg);
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Нужно ответить на сообщение"),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        self.request(
+            Some(self.config.public_channel_id),
+            self.bot
+                .send_message(
+                    self.config.public_channel_id,
+                    original_message
+                        .url()
+                        .expect("original message to have URL"),
+                )
+                .disable_link_preview(true),
+        )
+        .await?;
+
+        log::debug!("message posted");
+
+        let forwarded_message_url = self
+            .request(
+                Some(self.config.public_channel_id),
+                self.bot.forward_message(
+                    self.config.public_channel_id,
+                    chat_id,
+                    original_message.id,
+                ),
+            )
+            .await?
+            .url()
+            .expect("forwarded message to have URL");
+
+        log::debug!("original message forwarded");
+
+        let channel_name = self
+            .request(None, self.bot.get_chat(self.config.public_channel_id))
+            .await?
+            .title()
+            .unwrap_or("канал")
+            .to_owned();
+
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                format!("✔️ Запостил в <a href=\"{forwarded_message_url}\">{channel_name}</a>"),
+            ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn format_person_link(&self, details: &PersonDetails) -> String {
+        if details.link.is_empty() {
+            return format!("{} (гость)", details.display_name);
+        }
+        format!(
+            "<a href=\"{}\">{}</a>{}",
+            details.link,
+            details.display_name,
+            if details.resident { "®️" } else { "" }
+        )
+    }
+
+    fn format_visit_without_status(
+        &self,
+        v: &Visit,
+        details: &PersonDetails,
+        hosts: &VisitHosts,
+    ) -> String {
+        format!(
+            "{}{}{}",
+            self.format_person_link(details),
+            if !v.purpose.is_empty() {
+                format!(": \"{}\"", v.purpose)
+            } else {
+                "".to_owned()
+            },
+            match hosts.get(&(v.person, v.day)) {
+                Some(host) => format!(" (🔑 встречает {})", self.format_person_link(host)),
+                None => "".to_owned(),
+            }
+        )
+    }
+
+    /// Residents who agreed to open the space for guests, by the guest's visit
+    async fn get_visit_hosts(&self, from: NaiveDate, to: NaiveDate) -> Result<VisitHosts> {
+        let hosted = self
+            .backend()
+            .get_open_requests(from, to)
+            .await?
+            .into_iter()
+            .filter_map(|r| Some((r.requester, r.day, r.host?)))
+            .collect_vec();
+        let details = self
+            .fetch_persons_details(hosted.iter().map(|(_, _, host)| *host))
+            .await?;
+        Ok(hosted
+            .into_iter()
+            .map(|(requester, day, host)| ((requester, day), details[&host].clone()))
+            .collect())
+    }
+
+    async fn get_status(&self) -> Result<LiveStatus> {
+        let today = today();
+        let mut visits = self.backend().get_visits(today, today).await?;
+
+        let details = self
+            .fetch_persons_details(visits.iter().map(|v| v.person))
+            .await?;
+
+        visits.sort_by_key(|v| if details[&v.person].resident { 0 } else { 1 });
+
+        let hosts = self
+            .get_visit_hosts(today, today + TimeDelta::days(7))
+            .await?;
+
+        let mut status = String::new();
+
+        if let Some(motd) = self.backend().get_motd().await? {
+            status.push_str(&format!(
+                "📣 {}\n\n",
+                teloxide::utils::html::escape(&motd.text)
+            ));
+        }
+
+        let checked_in = visits
+            .iter()
+            .filter(|v| v.status == VisitStatus::CheckedIn)
+            .map(|v| self.format_visit_without_status(v, &details[&v.person], &hosts))
+            .join("\n");
+
+        let any_resident_inside = visits
+            .iter()
+            .any(|v| v.status == VisitStatus::CheckedIn && details[&v.person].resident);
+
+        let anybody_inside = visits.iter().any(|v| v.status == VisitStatus::CheckedIn);
+
+        let now = Utc::now();
+        let open_session = self
+            .backend()
+            .get_open_session()
+            .await?
+            .filter(|s| s.is_open(now));
+
+        if let Some(session) = &open_session {
+            status.push_str(&format!(
+                "🟢 Открыто до {}",
+                to_local(session.closes_at).format("%H:%M")
+            ));
+            if !session.note.is_empty() {
+                status.push_str(&format!(
+                    ": {}",
+                    teloxide::utils::html::escape(&session.note)
+                ));
+            }
+            let opener = self.fetch_person_details(session.opener).await?;
+            status.push_str(&format!(
+                "\n🔑 Открыл(а) {}",
+                self.format_person_link(&opener)
+            ));
+        } else if any_resident_inside {
+            status.push_str("🟢 Хакспейс сейчас открыт");
+        } else {
+            status.push_str("🔒 Хакспейс сейчас закрыт");
+            if anybody_inside {
+                status.push_str(", но кто-то из гостей внутри???");
+            }
+            status.push_str(
+                "\n\n💡 Если хочешь зайти, напиши /askopen — резиденты увидят просьбу, и я сообщу, если кто-то сможет прийти.",
+            );
+        }
+
+        let notices = self.backend().get_notices().await?;
+        if !notices.is_empty() {
+            status.push_str("\n\n📌 Объявления:\n");
+            status.push_str(&cap_lines(
+                notices
+                    .iter()
+                    .map(|n| format!("• {}", teloxide::utils::html::escape(&n.text)))
+                    .collect(),
+                STATUS_SECTION_MAX_LINES,
+            ));
+        }
+
+        let closures = self
+            .backend()
+            .get_closures(today, today + TimeDelta::days(7))
+            .await?;
+        if !closures.is_empty() {
+            status.push_str("\n\n🚧 Спейс закрыт:\n");
+            status.push_str(&Self::format_closures(&closures));
+        }
+
+        let open_requests = self
+            .backend()
+            .get_open_requests(today, today + TimeDelta::days(7))
+            .await?;
+        if !open_requests.is_empty() {
+            let persons = open_requests
+                .iter()
+                .flat_map(|r| [Some(r.requester), r.host])
+                .flatten()
+                .collect_vec();
+            let request_details = self.fetch_persons_details(persons).await?;
+            status.push_str("\n\n🙋 Просят открыть:\n");
+            status.push_str(&cap_lines(
+                open_requests
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "• {} — {}",
+                            self.format_person_link(&request_details[&r.requester]),
+                            match r.host {
+                                Some(host) => format!(
+                                    "{}, 🔑 встречает {}",
+                                    Self::format_open_request_time(r),
+                                    self.format_person_link(&request_details[&host])
+                                ),
+                                None => format!(
+                                    "{}, ⏳ ищем, кто откроет",
+                                    Self::format_open_request_time(r)
+                                ),
+                            }
+                        )
+                    })
+                    .collect(),
+                STATUS_SECTION_MAX_LINES,
+            ));
+        }
+
+        if !checked_in.is_empty() {
+            status.push_str("\n\n👷 Сейчас в хакспейсе:\n");
+            status.push_str(&checked_in);
+        }
+
+        let planned = visits
+            .iter()
+            .filter(|v| v.status == VisitStatus::Planned)
+            .map(|v| self.format_visit_without_status(v, &details[&v.person], &hosts))
+            .join("\n");
+
+        if !planned.is_empty() {
+            status.push_str("\n\n📅 Планировали зайти:\n");
+            status.push_str(&planned);
+        }
+
+        let left = visits
+            .iter()
+            .filter(|v| v.status == VisitStatus::CheckedOut)
+            .map(|v| self.format_visit_without_status(v, &details[&v.person], &hosts))
+            .join("\n");
+
+        if !left.is_empty() {
+            status.push_str("\n\n🌆 Уже ушли:\n");
+            status.push_str(&left);
+        }
+
+        let events = self
+            .backend()
+            .get_events(now, now + TimeDelta::days(7))
+            .await?;
+        let external_events = self
+            .backend()
+            .get_external_events(now, now + TimeDelta::days(7));
+        if !events.is_empty() || !external_events.is_empty() {
+            status.push_str("\n\n🎪 События на неделю:\n");
+            // counted per event, summaries from external calendars may span several lines
+            let shown = events.len().min(STATUS_SECTION_MAX_LINES);
+            let shown_external = external_events.len().min(STATUS_SECTION_MAX_LINES - shown);
+            status.push_str(
+                &[
+                    self.format_events(&events[..shown]).await?,
+                    Self::format_calendar_events(&external_events[..shown_external]),
+                ]
+                .iter()
+                .filter(|s| !s.is_empty())
+                .join("\n"),
+            );
+            let hidden = events.len() + external_events.len() - shown - shown_external;
+            if hidden > 0 {
+                status.push_str(&format!("\n…и ещё {hidden}"));
+            }
+        }
+
+        let week_visits = self
+            .backend()
+            .get_visits(today + TimeDelta::days(1), today + TimeDelta::days(7))
+            .await?;
+
+        let details = self
+            .fetch_persons_details(week_visits.iter().map(|v| v.person))
+            .await?;
+
+        let formatted_week_visits = self.format_visits(week_visits, &details, &hosts);
+
+        if !formatted_week_visits.is_empty() {
+            status.push_str("\n\n🗓️ Планы на неделю:\n\n");
+            status.push_str(&formatted_week_visits);
+        }
+
+        Ok(LiveStatus {
+            text: truncate_message(status),
+            open: any_resident_inside || open_session.is_some(),
+        })
+    }
+
+    async fn handle_status(&self, msg: &Message) -> Result<()> {
+        if let Some(status_message) = self.get_status_message(msg.chat.id, Self::message_topic(msg))
+        {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "Посмотри в <a href=\"{}\">закрепе</a>",
+                        Message::url_of(
+                            msg.chat.id,
+                            msg.chat.username(),
+                            status_message.message_id
+                        )
+                        .expect("should be able to create url of live status message")
+                    ),
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let status = self.get_status().await?;
+
+        self.request(Some(msg.chat.id), self.send_message_reply(msg, status.text))
+            .await?;
+
+        Ok(())
+    }
+
+    fn format_visit(&self, v: &Visit, details: &PersonDetails, hosts: &VisitHosts) -> String {
+        let status_str = match v.status {
+            VisitStatus::Planned => "",
+            VisitStatus::CheckedIn => " (сейчас в спейсе 👷)",
+            VisitStatus::CheckedOut => " (ушёл 🌆)",
+        };
+        format!(
+            "{}{}",
+            self.format_visit_without_status(v, details, hosts),
+            status_str
+        )
+    }
+
+    fn format_day<'a>(
+        &self,
+        vs: impl IntoIterator<Item = &'a Visit>,
+        details: &HashMap<Uid, PersonDetails>,
+        hosts: &VisitHosts,
+    ) -> String {
+        vs.into_iter()
+            .sorted_by_key(|v| if details[&v.person].resident { 0 } else { 1 })
+            .map(|v| self.format_visit(v, &details[&v.person], hosts))
+            .join("\n")
+    }
+
+    fn format_visits(
+        &self,
+        mut vs: Vec<Visit>,
+        details: &HashMap<Uid, PersonDetails>,
+        hosts: &VisitHosts,
+    ) -> String {
+        vs.sort_by_key(|v| v.day);
+
+        vs.chunk_by(|v1, v2| v1.day == v2.day)
+            .map(|vs| {
+                let day = vs[0].day;
+                format!(
+                    "{}:\n{}",
+                    format_date(day),
+                    self.format_day(vs, details, hosts)
+                )
+            })
+            .join("\n\n")
+    }
+
+    async fn render_visits(&self, query: &VisitsQuery) -> Result<(String, InlineKeyboardMarkup)> {
+        let (from, to) = query.range();
+        let mut visits = self.backend().get_visits(from, to).await?;
+
+        let details = self
+            .fetch_persons_details(visits.iter().map(|v| v.person))
+            .await?;
+
+        let filter_description = match query.filter {
+            VisitsFilter::All => String::new(),
+            VisitsFilter::Residents => {
+                visits.retain(|v| details[&v.person].resident);
+                " (только резиденты)".to_owned()
+            }
+            VisitsFilter::Person(person) => {
+                visits.retain(|v| v.person == person);
+                let person_details = self.fetch_person_details(person).await?;
+                format!(" (только {})", self.format_person_link(&person_details))
+            }
+            VisitsFilter::Purpose(ref keyword) => {
+                let keyword_lowercase = keyword.to_lowercase();
+                visits.retain(|v| v.purpose.to_lowercase().contains(&keyword_lowercase));
+                format!(" (про \"{keyword}\")")
+            }
+        };
+
+        let hosts = self.get_visit_hosts(from, to).await?;
+        let formatted_visits = self.format_visits(visits, &details, &hosts);
+
+        let external_events = self.backend().get_external_events(
+            CalendarTime::Date(from).to_utc(),
+            CalendarTime::Date(to + TimeDelta::days(1)).to_utc(),
+        );
+
+        let short_date = |date: NaiveDate| {
+            date.format_localized("%-d %B", Locale::ru_RU)
+                .to_string()
+                .to_lowercase()
+        };
+        let header = format!(
+            "🗓️ Планы посещений с {} по {}{}",
+            short_date(from),
+            short_date(to),
+            filter_description
+        );
+        let mut text = if formatted_visits.is_empty() {
+            format!("{header}\n\n😔 Нет никаких планов")
+        } else {
+            format!("{header}:\n\n{formatted_visits}")
+        };
+        let closures = self.backend().get_closures(from, to).await?;
+        if !closures.is_empty() {
+            text.push_str("\n\n🚧 Спейс закрыт:\n");
+            text.push_str(&Self::format_closures(&closures));
+        }
+        if !external_events.is_empty() {
+            text.push_str("\n\n📆 События из календаря:\n");
+            text.push_str(&Self::format_calendar_events(&external_events));
+        }
+
+        let mut buttons = Vec::new();
+        if query.page > 0 {
+            buttons.push(InlineKeyboardButton::callback(
+                "◀️",
+                CallbackData::GetVisits(query.with_page(query.view, query.page - 1)).encode(),
+            ));
+        }
+        buttons.push(match query.view {
+            VisitsView::Week => InlineKeyboardButton::callback(
+                "📅 По месяцам",
+                CallbackData::GetVisits(query.with_page(VisitsView::Month, 0)).encode(),
+            ),
+            VisitsView::Month => InlineKeyboardButton::callback(
+                "📅 По неделям",
+                CallbackData::GetVisits(query.with_page(VisitsView::Week, 0)).encode(),
+            ),
+        });
+        if query.page < query.last_page() {
+            buttons.push(InlineKeyboardButton::callback(
+                "▶️",
+                CallbackData::GetVisits(query.with_page(query.view, query.page + 1)).encode(),
+            ));
+        }
+
+        Ok((
+            truncate_message(text),
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![buttons],
+            },
+        ))
+    }
+
+    async fn handle_get_visits(&self, msg: &Message) -> Result<()> {
+        let query = parse_visits_query(
+            Self::message_author(msg),
+            Self::message_text(msg),
+            msg.reply_to_message()
+                .and_then(|m| m.from.as_ref())
+                .filter(|u| !u.is_bot)
+                .map(|u| Uid(u.id)),
+        );
+
+        let (text, markup) = self.render_visits(&query).await?;
+
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(msg, text).reply_markup(markup),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn format_stats(&self, period: StatsPeriod, stats: &Stats) -> Result<String> {
+        const WEEKDAYS: [&str; 7] = ["пн", "вт", "ср", "чт", "пт", "сб", "вс"];
+
+        let period_name = match period {
+            StatsPeriod::Week => "неделю",
+            StatsPeriod::Month => "месяц",
+            StatsPeriod::Year => "год",
+        };
+        let mut text = format!(
+            "📊 Статистика за {period_name} ({} — {})\n\n",
+            stats.from.format_localized("%-d %B", Locale::ru_RU),
+            stats.to.format_localized("%-d %B", Locale::ru_RU),
+        );
+
+        if stats.total_visits == 0 {
+            text.push_str("🦗 Никто не заходил");
+            return Ok(text);
+        }
+
+        text.push_str(&format!(
+            "👷 Визитов: {}, разных людей: {}\n®️ Резиденты: {}, гости: {}\n🌆 В среднем за вечер: {:.1}",
+            stats.total_visits,
+            stats.unique_visitors,
+            stats.resident_visits,
+            stats.guest_visits,
+            stats.average_per_evening,
+        ));
+
+        let busiest = stats
+            .visits_by_weekday
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .sorted_by_key(|(day, count)| (std::cmp::Reverse(**count), *day))
+            .take(3)
+            .map(|(day, count)| format!("{} ({count})", WEEKDAYS[day]))
+            .join(", ");
+        text.push_str(&format!("\n📅 Самые загруженные дни: {busiest}"));
+
+        let details = self
+            .fetch_persons_details(stats.top_visitors.iter().map(|t| Uid::from(t.person)))
+            .await?;
+        text.push_str("\n\n🏆 Чаще всех заходили:\n");
+        text.push_str(
+            &stats
+                .top_visitors
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    format!(
+                        "{}. {} — {}",
+                        i + 1,
+                        self.format_person_link(&details[&Uid::from(t.person)]),
+                        t.visits
+                    )
+                })
+                .join("\n"),
+        );
+
+        Ok(text)
+    }
+
+    async fn handle_stats(&self, msg: &Message) -> Result<()> {
+        let Some(period) = StatsPeriod::parse(Self::message_text(msg)) else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    "❌ Период может быть \"неделя\", \"месяц\" или \"год\"",
+                ),
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let (from, to) = period.range(today());
+        let stats = self.backend().get_stats(from, to).await?;
+        let text = self.format_stats(period, &stats).await?;
+
+        self.request(Some(msg.chat.id), self.send_message_reply(msg, text))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn render_me(&self, person: Uid) -> Result<(String, InlineKeyboardMarkup)> {
+        let today = today();
+        let visits = self
+            .backend()
+            .get_person_visits(
+                person,
+                today - TimeDelta::days(ME_HISTORY_DAYS),
+                today + TimeDelta::days(VISITS_HORIZON_DAYS),
+            )
+            .await?;
+        let details = self.fetch_person_details(person).await?;
+
+        let today_visit = visits.iter().find(|v| v.day == today);
+        let current = match today_visit.map(|v| v.status) {
+            Some(VisitStatus::CheckedIn) => "👷 Сейчас в спейсе",
+            Some(VisitStatus::CheckedOut) => "🌆 Сегодня уже был в спейсе",
+            Some(VisitStatus::Planned) => "🗓️ Планируешь зайти сегодня",
+            None => "🏠 Сейчас не в спейсе",
+        };
+
+        let plans = visits
+            .iter()
+            .filter(|v| v.day >= today && v.status == VisitStatus::Planned)
+            .take(ME_MAX_PLANS)
+            .collect_vec();
+        let attended = visits
+            .iter()
+            .filter(|v| v.day <= today && v.status != VisitStatus::Planned)
+            .collect_vec();
+        let missed = visits
+            .iter()
+            .filter(|v| v.day < today && v.status == VisitStatus::Planned)
+            .count();
+
+        let format_visit = |v: &Visit| {
+            format!(
+                "• {}{}",
+                format_date(v.day),
+                if v.purpose.is_empty() {
+                    String::new()
+                } else {
+                    format!(": \"{}\"", v.purpose)
+                }
+            )
+        };
+
+        let mut text = format!("👤 {}\n\n{current}", self.format_person_link(&details));
+
+        if plans.is_empty() {
+            text.push_str("\n\n🗓️ Планов нет, /planvisit чтобы запланировать");
+        } else {
+            text.push_str("\n\n🗓️ Планы:\n");
+            text.push_str(&plans.iter().map(|v| format_visit(v)).join("\n"));
+        }
+
+        if !attended.is_empty() {
+            text.push_str("\n\n🕰️ Недавние визиты:\n");
+            text.push_str(
+                &attended
+                    .iter()
+                    .rev()
+                    .take(ME_RECENT_VISITS)
+                    .map(|v| format_visit(v))
+                    .join("\n"),
+            );
+        }
+
+        text.push_str(&format!(
+            "\n\n📊 За последние {ME_HISTORY_DAYS} дней: визитов — {}, несостоявшихся планов — {missed}",
+            attended.len()
+        ));
+
+        let markup = InlineKeyboardMarkup {
+            inline_keyboard: plans
+                .iter()
+                .map(|v| {
+                    vec![InlineKeyboardButton::callback(
+                        format!(
+                            "❌ Отменить {}",
+                            format_close_date(v.day)
+                                .map(str::to_owned)
+                                .unwrap_or_else(|| v.day.format("%d.%m").to_string())
+                        ),
+                        CallbackData::MeUnplan { person, day: v.day }.encode(),
+                    )]
+                })
+                .collect(),
+        };
+
+        Ok((truncate_message(text), markup))
+    }
+
+    async fn handle_me(self: &Arc<Self>, msg: &Message) -> Result<()> {
+        let (text, markup) = self.render_me(Self::message_author(msg)).await?;
+
+        let reply = self
+            .request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, text).reply_markup(markup),
+            )
+            .await?;
+
+        if !msg.chat.is_private() {
+            self.delete_message_later(reply.chat.id, reply.id, ME_AUTO_DELETE);
+        }
+
+        Ok(())
+    }
+
+    async fn handle_me_unplan_callback(
+        &self,
+        q: &CallbackQuery,
+        person: Uid,
+        day: NaiveDate,
+    ) -> Result<String> {
+        if person != Uid(q.from.id) {
+            return Ok("Это не твоя кнопка".to_owned());
+        }
+
+        let toast = if self.backend().unplan_visit(person, day).await? {
+            format!("🤔 Вычеркнул тебя на {}", format_date(day))
+        } else {
+            format!("У тебя и так не было планов на {}", format_date(day))
+        };
+
+        if let Some(msg) = q.regular_message() {
+            let (text, markup) = self.render_me(person).await?;
+            self.request(
+                Some(msg.chat.id),
+                self.bot
+                    .edit_message_text(msg.chat.id, msg.id, text)
+                    .parse_mode(ParseMode::Html)
+                    .disable_link_preview(true)
+                    .reply_markup(markup),
+            )
+            .await?;
+        }
+
+        Ok(toast)
+    }
+
+    fn delete_message_later(
+        self: &Arc<Self>,
+        chat_id: ChatId,
+        message_id: MessageId,
+        delay: Duration,
+    ) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = self_clone
+                .background_request(
+                    Some(chat_id),
+                    self_clone.bot.delete_message(chat_id, message_id),
+                )
+                .await
+            {
+                log::warn!("Failed to delete message: {e}");
+            }
+        });
+    }
+
+    async fn handle_get_visits_callback(
+        &self,
+        q: &CallbackQuery,
+        query: &VisitsQuery,
+    ) -> Result<()> {
+        let Some(msg) = q.regular_message() else {
+            return Ok(());
+        };
+
+        let (text, markup) = self.render_visits(query).await?;
+
+        self.request(
+            Some(msg.chat.id),
+            self.bot
+                .edit_message_text(msg.chat.id, msg.id, text)
+                .parse_mode(ParseMode::Html)
+                .disable_link_preview(true)
+                .reply_markup(markup),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_status_messages(
+        pool: &SqlitePool,
+        public_chat_id: ChatId,
+    ) -> Result<Vec<LiveStatusMessage>> {
+        // rows migrated from the single-message table don't know the public chat id
+        sqlx::query!(
+            "UPDATE status_messages SET chat_id = ?1 WHERE chat_id = 0",
+            public_chat_id.0
+        )
+        .execute(pool)
+        .await?;
+        Ok(
+            sqlx::query!("SELECT chat_id, thread_id, message_id FROM status_messages")
+                .map(|r| LiveStatusMessage {
+                    chat_id: ChatId(r.chat_id),
+                    thread_id: (r.thread_id != 0)
+                        .then_some(ThreadId(MessageId(r.thread_id as i32))),
+                    message_id: MessageId(r.message_id as i32),
+                })
+                .fetch_all(pool)
+                .await?,
+        )
+    }
+
+    async fn save_status_message(pool: &SqlitePool, message: &LiveStatusMessage) -> Result<()> {
+        let thread_id = message.thread_id.map(|t| t.0.0).unwrap_or(0);
+        sqlx::query!(
+            "INSERT INTO status_messages (chat_id, thread_id, message_id) VALUES (?1, ?2, ?3)
+            ON CONFLICT (chat_id, thread_id) DO UPDATE SET message_id = ?3",
+            message.chat_id.0,
+            thread_id,
+            message.message_id.0,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_status_message(
+        pool: &SqlitePool,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+    ) -> Result<()> {
+        let thread_id = thread_id.map(|t| t.0.0).unwrap_or(0);
+        sqlx::query!(
+            "DELETE FROM status_messages WHERE chat_id = ?1 AND thread_id = ?2",
+            chat_id.0,
+            thread_id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_status_message(&self, message: LiveStatusMessage) -> Result<()> {
+        Self::save_status_message(self.backend().pool(), &message).await?;
+        let mut messages = self.status_messages.write().unwrap();
+        messages.retain(|m| (m.chat_id, m.thread_id) != (message.chat_id, message.thread_id));
+        messages.push(message);
+        Ok(())
+    }
+
+    async fn remove_status_message(
+        &self,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+    ) -> Result<()> {
+        Self::delete_status_message(self.backend().pool(), chat_id, thread_id).await?;
+        self.status_messages
+            .write()
+            .unwrap()
+            .retain(|m| (m.chat_id, m.thread_id) != (chat_id, thread_id));
+        Ok(())
+    }
+
+    fn get_status_message(
+        &self,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+    ) -> Option<LiveStatusMessage> {
+        self.status_messages
+            .read()
+            .unwrap()
+            .iter()
+            .find(|m| (m.chat_id, m.thread_id) == (chat_id, thread_id))
+            .copied()
+    }
+
+    fn get_status_messages(&self) -> Vec<LiveStatusMessage> {
+        self.status_messages.read().unwrap().clone()
+    }
+
+    fn live_status_target(&self, msg: &Message) -> (ChatId, Option<ThreadId>) {
+        if Self::message_text(msg) == "канал" {
+            return (self.config.public_channel_id, None);
+        }
+        (msg.chat.id, Self::message_topic(msg))
+    }
+
+    async fn handle_live_status(&self, msg: &Message) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+
+        let (chat_id, thread_id) = self.live_status_target(msg);
+
+        if let Some(old) = self.get_status_message(chat_id, thread_id) {
+            self.request(
+                Some(chat_id),
+                self.bot
+                    .unpin_chat_message(chat_id)
+                    .message_id(old.message_id),
+            )
+            .await?;
+            self.remove_status_message(chat_id, thread_id).await?;
+        }
+
+        let live_status = self.get_status().await?;
+        self.post_live_status_message(chat_id, thread_id, &live_status.text)
+            .await?;
+
+        if chat_id != msg.chat.id {
+            self.acknowledge_message(msg).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn post_live_status_message(
+        &self,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+        live_status: &str,
+    ) -> Result<()> {
+        let message_id = self
+            .send_live_status_message(chat_id, thread_id, live_status)
+            .await?;
+        self.pin_live_status_message(chat_id, message_id).await
+    }
+
+    /// Sends and remembers a new live status message without pinning it
+    async fn send_live_status_message(
+        &self,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+        live_status: &str,
+    ) -> Result<MessageId> {
+        let message_id = self
+            .request(
+                Some(chat_id),
+                self.send_message_to(chat_id, Self::get_full_live_status(live_status))
+                    .with_payload_mut(|p| p.message_thread_id = thread_id)
+                    .reply_markup(Self::live_status_markup()),
+            )
+            .await?
+            .id;
+        self.set_status_message(LiveStatusMessage {
+            chat_id,
+            thread_id,
+            message_id,
+        })
+        .await?;
+        Ok(message_id)
+    }
+
+    async fn pin_live_status_message(&self, chat_id: ChatId, message_id: MessageId) -> Result<()> {
+        self.request(
+            Some(chat_id),
+            self.bot
+                .pin_chat_message(chat_id, message_id)
+                .disable_notification(true),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn handle_unlive_status(&self, msg: &Message) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+
+        let (chat_id, thread_id) = self.live_status_target(msg);
+
+        if let Some(old) = self.get_status_message(chat_id, thread_id) {
+            self.request(
+                Some(chat_id),
+                self.bot
+                    .unpin_chat_message(chat_id)
+                    .message_id(old.message_id),
+            )
+            .await?;
+            self.remove_status_message(chat_id, thread_id).await?;
+        }
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    fn get_full_live_status(live_status: &str) -> String {
+        live_status.to_owned()
+            + "\n\nОбновлено: "
+            + &crate::utils::now()
+                .format_localized("%c %Z", Locale::ru_RU)
+                .to_string()
+    }
+
+    async fn update_live_status_message(&self, live_status: &str, check_pins: bool) -> Result<()> {
+        for message in self.get_status_messages() {
+            if let Err(e) = self
+                .update_live_status_message_in(&message, live_status, check_pins)
+                .await
+            {
+                log::error!("Error updating status message {:?}: {:?}", message, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_live_status_message_in(
+        &self,
+        message: &LiveStatusMessage,
+        live_status: &str,
+        check_pin: bool,
+    ) -> Result<()> {
+        let edited = self
+            .background_request(
+                Some(message.chat_id),
+                self.bot
+                    .edit_message_text(
+                        message.chat_id,
+                        message.message_id,
+                        Self::get_full_live_status(live_status),
+                    )
+                    .parse_mode(ParseMode::Html)
+                    .disable_link_preview(true)
+                    .reply_markup(Self::live_status_markup()),
+            )
+            .await;
+        match edited {
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+            Err(RequestError::Api(
+                ApiError::MessageToEditNotFound | ApiError::MessageCantBeEdited,
+            )) => {
+                log::warn!("Live status message {:?} is gone, recreating", message);
+                return self
+                    .recreate_live_status_message(message, live_status)
+                    .await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        if check_pin {
+            // only the latest pin is visible: an older one on top means ours was unpinned, while
+            // pins made after ours can't be told apart from ours being removed and are left alone
+            let chat = self
+                .background_request(None, self.bot.get_chat(message.chat_id))
+                .await?;
+            if chat
+                .pinned_message
+                .is_none_or(|pinned| pinned.id.0 < message.message_id.0)
+            {
+                log::info!("Live status message {:?} was unpinned, pinning", message);
+                self.pin_live_status_message(message.chat_id, message.message_id)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn recreate_live_status_message(
+        &self,
+        message: &LiveStatusMessage,
+        live_status: &str,
+    ) -> Result<()> {
+        let e = match self
+            .send_live_status_message(message.chat_id, message.thread_id, live_status)
+            .await
+        {
+            Ok(message_id) => {
+                if let Err(e) = self
+                    .pin_live_status_message(message.chat_id, message_id)
+                    .await
+                {
+                    // the new message is remembered, so pinning is retried with the next pin check
+                    log::warn!(
+                        "Failed to pin recreated live status message in {:?}: {:?}",
+                        message.chat_id,
+                        e
+                    );
+                }
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        log::error!(
+            "Failed to recreate live status message {:?}: {:?}",
+            message,
+            e
+        );
+
+        self.remove_status_message(message.chat_id, message.thread_id)
+            .await?;
+        self.request(
+            Some(self.config.alert_chat_id),
+            self.bot.send_message(
+                self.config.alert_chat_id,
+                format!(
+                    "⚠️ Закреп со статусом в чате {} пропал, и пересоздать его не получилось, так что я про него забыл",
+                    message.chat_id
+                ),
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn message_text(msg: &Message) -> &str {
+        strip_command(msg.text().expect("message to have text"))
+    }
+
+    fn message_topic(msg: &Message) -> Option<ThreadId> {
+        if msg.is_topic_message {
+            msg.thread_id
+        } else {
+            None
+        }
+    }
+
+    fn message_author(msg: &Message) -> Uid {
+        Uid(msg.from.as_ref().expect("message to have author").id)
+    }
+
+    fn parse_visit_message(msg: &Message) -> VisitUpdate {
+        parse_visit_text(Self::message_author(msg), Self::message_text(msg))
+    }
+
+    fn common_modifiers(send_message: JsonRequest<SendMessage>) -> JsonRequest<SendMessage> {
+        send_message
+            .disable_notification(true)
+            .parse_mode(ParseMode::Html)
+            .disable_link_preview(true)
+    }
+
+    fn send_message_public_chat(&self, text: impl Into<String>) -> JsonRequest<SendMessage> {
+        self.send_message_to(self.config.public_chat_id, text)
+    }
+
+    fn send_message_to(
+        &self,
+        chat_id: ChatId,
+        text: impl Into<String>,
+    ) -> JsonRequest<SendMessage> {
+        Self::common_modifiers(self.bot.send_message(chat_id, text))
+    }
+
+    fn send_message_reply(
+        &self,
+        msg: &Message,
+        text: impl Into<String>,
+    ) -> JsonRequest<SendMessage> {
+        Self::common_modifiers(
+            self.bot
+                .send_message(msg.chat.id, text)
+                .with_payload_mut(|p| p.message_thread_id = msg.thread_id)
+                .reply_to(msg.id),
+        )
+    }
+
+    async fn acknowledge_message(&self, msg: &Message) -> Result<()> {
+        self.request(
+            None,
+            self.bot
+                .set_message_reaction(msg.chat.id, msg.id)
+                .reaction(vec![ReactionType::Emoji {
+                    emoji: "✍".to_owned(),
+                }]),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn handle_plan_visit(&self, msg: &Message) -> Result<()> {
+        let (day, text) = parse_day_purpose(Self::message_text(msg), today());
+        let Some(target) = self.resolve_target(msg, text).await? else {
+            return Ok(());
+        };
+
+        // residents may still need to come, e.g. for the renovation itself
+        if let Some(warning) = self.closure_warning(day).await? {
+            let resident = self.is_resident(Self::message_author(msg).0).await?;
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "{warning}{}",
+                        if resident {
+                            "\n\nЗаписал, раз ты резидент"
+                        } else {
+                            "\n\nВыбери другой день"
+                        }
+                    ),
+                ),
+            )
+            .await?;
+            if !resident {
+                return Ok(());
+            }
+        }
+
+        self.backend()
+            .set_actor(target.person, day, target.actor)
+            .await?;
+        self.backend()
+            .plan_visit(target.person, day, optional_text(&target.text))
+            .await?;
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_unplan_visit(&self, msg: &Message) -> Result<()> {
+        let visit_update = Self::parse_visit_message(msg);
+
+        self.backend()
+            .unplan_visit(visit_update.person, visit_update.day)
+            .await?;
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    async fn handle_check_in(&self, msg: &Message) -> Result<()> {
+        let Some(target) = self.resolve_target(msg, Self::message_text(msg)).await? else {
+            return Ok(());
+        };
+
+        self.backend()
+            .set_actor(target.person, today(), target.actor)
+            .await?;
+        self.backend()
+            .check_in(target.person, optional_text(&target.text))
+            .await?;
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    pub async fn announce_check_in(&self, visit_update: &VisitUpdate) -> Result<()> {
+        self.post_announcement(
+            format!(
+                "👷 {}{} пришёл в хакспейс{}",
+                self.format_person_link(&self.fetch_person_details(visit_update.person).await?),
+                self.format_actor(visit_update.person, visit_update.day)
+                    .await?,
+                visit_update
+                    .purpose
+                    .as_deref()
+                    .map(|p| { format!(": \"{p}\"") })
+                    .unwrap_or_default()
+            ),
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![vec![
+                    InlineKeyboardButton::callback(
+                        "👷 Я тоже в спейсе",
+                        CallbackData::CheckIn.encode(),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "🌆 А я уже ушёл",
+                        CallbackData::CheckOut.encode(),
+                    ),
+                ]],
+            },
+        )
+        .await
+    }
+
+    async fn handle_purpose(&self, msg: &Message) -> Result<()> {
+        let (day, purpose) = parse_day_purpose(Self::message_text(msg), today());
+        if purpose.is_empty() {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    "✏️ Напиши новое описание: /purpose [YYYY-MM-DD] описание",
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if !self
+            .backend()
+            .set_purpose(Self::message_author(msg), day, purpose.to_owned())
+            .await?
+        {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "🤷 У тебя нет визита на {}, сначала /planvisit или /checkin",
+                        format_date(day)
+                    ),
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
+    }
+
+    /// Makes the stored purpose follow edits of the command that set it
+    async fn handle_edited_message(&self, msg: &Message, cmd: Command) -> Result<()> {
+        // the day the message was originally sent, edits can come days later
+        let sent_on = day_of(msg.date);
+        let (day, text) = match cmd {
+            Command::PlanVisit | Command::Purpose => {
+                parse_day_purpose(Self::message_text(msg), sent_on)
+            }
+            Command::CheckIn => (sent_on, Self::message_text(msg).trim()),
+            _ => return Ok(()),
+        };
+
+        if cmd == Command::Purpose {
+            if text.is_empty() {
+                return Ok(());
+            }
+            self.backend()
+                .set_purpose(Self::message_author(msg), day, text.to_owned())
+                .await?;
+            return Ok(());
+        }
+
+        let Some(target) = self.resolve_target(msg, text).await? else {
+            return Ok(());
+        };
+        self.backend()
+            .set_purpose(target.person, day, target.text)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn handle_check_out(&self, msg: &Message) -> Result<()> {
+        let Some(target) = self.resolve_target(msg, Self::message_text(msg)).await? else {
+            return Ok(());
+        };
+
+        self.backend()
+            .set_actor(target.person, today(), target.actor)
+            .await?;
+        let previous = self.backend().get_visit(target.person, today()).await?;
+        self.backend().check_out(target.person).await?;
+
+        self.acknowledge_message(msg).await?;
+
+        if previous.is_some_and(|v| v.status == VisitStatus::CheckedIn) {
+            self.check_last_resident_out(target.person).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Figures out who the command is about: the author, or, if the author is a resident,
+    /// a guest, a mentioned user or the author of the replied-to message
+    async fn resolve_target(&self, msg: &Message, text: &str) -> Result<Option<CommandTarget>> {
+        let author = Self::message_author(msg);
+        let (other, text) = if let Some((name, rest)) = parse_guest(text) {
+            (Some(OtherPerson::Guest(name)), rest.to_owned())
+        } else if let Some((user, rest)) = self.leading_mention(msg, text)
+            && user != author
+            // "паяю с @bob" is not about bob, and non-residents mentioning someone act for themselves
+            && self.is_resident(author.0).await?
+        {
+            (Some(OtherPerson::User(user)), rest.to_owned())
+        } else if let Some(user) = Self::replied_to_user(msg)
+            && user != author
+            // replying is also just how people talk, only residents act on behalf of others this way
+            && self.is_resident(author.0).await?
+        {
+            (Some(OtherPerson::User(user)), text.to_owned())
+        } else {
+            (None, text.to_owned())
+        };
+
+        let other = match other {
+            None => None,
+            Some(OtherPerson::User(user)) if user == author => None,
+            Some(other) => Some(other),
+        };
+        let Some(other) = other else {
+            return Ok(Some(CommandTarget {
+                person: author,
+                actor: None,
+                text,
+            }));
+        };
+
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(None);
+        }
+
+        let person = match other {
+            OtherPerson::Guest(name) => self.backend().guest(name.to_owned(), author).await?,
+            OtherPerson::User(user) => user,
+        };
+        Ok(Some(CommandTarget {
+            person,
+            actor: Some(author),
+            text,
+        }))
+    }
+
+    /// Returns the user mentioned at the very start of `text` and the text after the mention
+    fn leading_mention<'a>(&self, msg: &Message, text: &'a str) -> Option<(Uid, &'a str)> {
+        msg.parse_entities()?.iter().find_map(|entity| {
+            let rest = strip_leading_mention(text, entity.text())?;
+            let user = match entity.kind() {
+                MessageEntityKind::TextMention { user } => Uid(user.id),
+                MessageEntityKind::Mention => {
+                    let username = entity.text().trim_start_matches('@').to_lowercase();
+                    Uid(*self.known_usernames.read().unwrap().get(&username)?)
+                }
+                _ => return None,
+            };
+            Some((user, rest))
+        })
+    }
+
+    fn replied_to_user(msg: &Message) -> Option<Uid> {
+        let reply = msg.reply_to_message()?;
+        // In topics, messages without an explicit reply point to the message that started the topic
+        if msg.is_topic_message && msg.thread_id.is_some_and(|t| t.0 == reply.id) {
+            return None;
+        }
+        let user = reply.from.as_ref()?;
+        if user.is_bot {
+            return None;
+        }
+        Some(Uid(user.id))
+    }
+
+    /// " (отметил X)" if the visit was changed by someone else
+    async fn format_actor(&self, person: Uid, day: NaiveDate) -> Result<String> {
+        let Some(actor) = self.backend().get_actor(person, day).await? else {
+            return Ok(String::new());
+        };
+        Ok(format!(
+            " (отметил {})",
+            self.format_person_link(&self.fetch_person_details(actor).await?)
+        ))
+    }
+
+    /// Sends the lock-up checklist if `person` was the last resident inside
+    async fn check_last_resident_out(&self, person: Uid) -> Result<()> {
+        if self.config.lockup_checklist.is_empty()
+            || person.is_guest()
+            || !self.is_resident(person.0).await?
+        {
+            return Ok(());
+        }
+        let inside = self
+            .backend()
+            .get_visits(today(), today())
+            .await?
+            .into_iter()
+            .filter(|v| v.status == VisitStatus::CheckedIn && v.person != person)
+            .map(|v| v.person)
+            .collect_vec();
+        if !self.residents(inside.iter().copied()).await?.is_empty() {
+            return Ok(());
+        }
+        self.send_lockup_checklist(person, &inside, false).await
+    }
+
+    fn lockup_markup(&self, lockup: &Lockup) -> InlineKeyboardMarkup {
+        let mut rows = self
+            .config
+            .lockup_checklist
+            .iter()
+            .take(LOCKUP_MAX_ITEMS)
+            .enumerate()
+            .map(|(i, item)| {
+                let mark = if lockup.is_checked(i) { "✅" } else { "⬜" };
+                vec![InlineKeyboardButton::callback(
+                    format!("{mark} {item}"),
+                    CallbackData::Lockup {
+                        lockup: lockup.id,
+                        item: Some(i as u8),
+                    }
+                    .encode(),
+                )]
+            })
+            .collect_vec();
+        rows.push(vec![InlineKeyboardButton::callback(
+            "🔐 Всё, ухожу",
+            CallbackData::Lockup {
+                lockup: lockup.id,
+                item: None,
+            }
+            .encode(),
+        )]);
+        InlineKeyboardMarkup::new(rows)
+    }
+
+    /// `guests` are the ones still checked in, or the ones checked out by /close if `closed`
+    async fn send_lockup_checklist(&self, person: Uid, guests: &[Uid], closed: bool) -> Result<()> {
+        let details = self.fetch_persons_details(guests.iter().copied()).await?;
+        let audit_details = if guests.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "гости в спейсе: {}",
+                guests.iter().map(|g| &details[g].display_name).join(", ")
+            )
+        };
+        let id = self
+            .backend()
+            .start_lockup(person, today(), audit_details)
+            .await?;
+        let Some(lockup) = self.backend().get_lockup(id).await? else {
+            return Ok(());
+        };
+
+        let mut text = "🔐 Похоже, ты уходишь последним из резидентов. Перед уходом пройдись по списку и отметь сделанное:".to_owned();
+        if !guests.is_empty() {
+            let links = guests
+                .iter()
+                .map(|g| self.format_person_link(&details[g]))
+                .join(", ");
+            if closed {
+                text.push_str(&format!(
+                    "\n\n⚠️ В спейсе ещё были отмечены гости: {links}. Я отметил их ушедшими, убедись, что внутри никого не осталось"
+                ));
+            } else {
+                text.push_str(&format!(
+                    "\n\n⚠️ В спейсе всё ещё отмечены гости: {links}. Убедись, что они ушли, и отметь их через /checkout"
+                ));
+            }
+        }
+
+        let chat_id = ChatId::from(person.0);
+        if let Err(e) = self
+            .request(
+                Some(chat_id),
+                self.send_message_to(chat_id, text)
+                    .reply_markup(self.lockup_markup(&lockup)),
+            )
+            .await
+        {
+            log::warn!("Failed to send lock-up checklist to {:?}: {e}", person);
+        }
+        Ok(())
+    }
+
+    async fn handle_lockup_callback(
+        &self,
+        q: &CallbackQuery,
+        id: i64,
+        item: Option<u8>,
+    ) -> Result<String> {
+        let Some(lockup) = self.backend().get_lockup(id).await? else {
+            return Ok("🤷 Нет такого списка".to_owned());
+        };
+        if lockup.person != Uid(q.from.id) {
+            return Ok("Это не твой список".to_owned());
+        }
+        if lockup.confirmed {
+            return Ok("Уже записал, спасибо".to_owned());
+        }
+        let items = &self.config.lockup_checklist;
+
+        match item {
+            Some(item) => {
+                let Some(label) = items.get(item as usize) else {
+                    return Ok("🤷 Этого пункта больше нет в списке".to_owned());
+                };
+                if !self.backend().toggle_lockup_item(id, item).await? {
+                    return Ok("Уже записал, спасибо".to_owned());
+                }
+                let Some(lockup) = self.backend().get_lockup(id).await? else {
+                    return Ok("🤷 Нет такого списка".to_owned());
+                };
+                if let Some(msg) = q.regular_message() {
+                    self.request(
+                        Some(msg.chat.id),
+                        self.bot
+                            .edit_message_reply_markup(msg.chat.id, msg.id)
+                            .reply_markup(self.lockup_markup(&lockup)),
+                    )
+                    .await?;
+                }
+                let mark = if lockup.is_checked(item as usize) {
+                    "✅"
+                } else {
+                    "⬜"
+                };
+                Ok(format!("{mark} {label}"))
+            }
+            None => {
+                let (done, missed): (Vec<_>, Vec<_>) = items
+                    .iter()
+                    .take(LOCKUP_MAX_ITEMS)
+                    .enumerate()
+                    .partition(|(i, _)| lockup.is_checked(*i));
+                let mut audit_details =
+                    format!("отмечено {}/{}", done.len(), done.len() + missed.len());
+                if !missed.is_empty() {
+                    audit_details.push_str(&format!(
+                        ", не отмечено: {}",
+                        missed.iter().map(|(_, item)| item).join(", ")
+                    ));
+                }
+                if !self.backend().confirm_lockup(id, audit_details).await? {
+                    return Ok("Уже записал, спасибо".to_owned());
+                }
+
+                if let Some(msg) = q.regular_message() {
+                    let summary = items
+                        .iter()
+                        .take(LOCKUP_MAX_ITEMS)
+                        .enumerate()
+                        .map(|(i, item)| {
+                            let mark = if lockup.is_checked(i) { "✅" } else { "⬜" };
+                            format!("{mark} {}", teloxide::utils::html::escape(item))
+                        })
+                        .join("\n");
+                    self.request(
+                        Some(msg.chat.id),
+                        self.bot
+                            .edit_message_text(
+                                msg.chat.id,
+                                msg.id,
+                                format!("🔐 Спасибо, записал, что спейс закрыт:\n\n{summary}"),
+                            )
+                            .parse_mode(ParseMode::Html),
+                    )
+                    .await?;
+                }
+
+                Ok(if missed.is_empty() {
+                    "🔐 Спасибо, хорошего вечера!".to_owned()
+                } else {
+                    "🔐 Записал, но отмечено не всё".to_owned()
+                })
+            }
+        }
+    }
+
+    fn format_open_request_time(request: &OpenRequest) -> String {
+        match request.time {
+            Some(time) => format!("{}, к {}", format_date(request.day), time.format("%H:%M")),
+            None => format_date(request.day),
         }
-        Ok(true)
     }
 
-    async fn handle_post_live(&self, msg: &Message) -> Result<()> {
-        let Some(chat_id) = self.check_is_public_chat_msg(msg).await? else {
-            return Ok(());
-        };
-        if !self.check_author_is_resident(msg).await? {
-            return Ok(());
+    fn format_open_request(
+        &self,
+        request: &OpenRequest,
+        requester: &PersonDetails,
+        host: Option<&PersonDetails>,
+    ) -> String {
+        let mut text = format!(
+            "🙋 {} просит открыть спейс {}",
+            self.format_person_link(requester),
+            Self::format_open_request_time(request)
+        );
+        if !request.comment.is_empty() {
+            text.push_str(&format!(
+                ": {}",
+                teloxide::utils::html::escape(&request.comment)
+            ));
         }
+        match host {
+            Some(host) => text.push_str(&format!(
+                "\n\n🔑 Встречает {}",
+                self.format_person_link(host)
+            )),
+            None => text.push_str("\n\nКто сможет прийти?"),
+        }
+        text
+    }
 
-        let Some(original_message) = msg.reply_to_message() else {
-            log::debug!("message is not a reply: {:?}", msg);
-            self.send_message_reply(msg, "❌ Нужно ответить на сообщение")
+    async fn handle_ask_open(&self, msg: &Message) -> Result<()> {
+        let author = Self::message_author(msg);
+        if let Some(rest) = Self::message_text(msg)
+            .trim_start()
+            .strip_prefix("отменить")
+        {
+            return self.handle_ask_open_cancel(msg, rest).await;
+        }
+        let (day, rest) = parse_day_purpose(Self::message_text(msg), today());
+        let (time, comment) = parse_optional_time(rest);
+
+        let error = if day < today() {
+            Some("❌ Этот день уже прошёл".to_owned())
+        } else {
+            self.closure_warning(day).await?
+        };
+        if let Some(error) = error {
+            self.request(Some(msg.chat.id), self.send_message_reply(msg, error))
                 .await?;
             return Ok(());
-        };
+        }
 
-        self.bot
-            .send_message(
-                self.config.public_channel_id,
-                original_message
-                    .url()
-                    .expect("original message to have URL"),
+        let Some(request) = self
+            .backend()
+            .create_open_request(author, day, time, comment.to_owned())
+            .await?
+        else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "Ты уже просил открыть спейс {}, /askopen отменить {} чтобы отозвать просьбу",
+                        format_date(day),
+                        day.format("%Y-%m-%d")
+                    ),
+                ),
             )
-            .disable_link_preview(true)
             .await?;
+            return Ok(());
+        };
 
-        log::debug!("message posted");
-
-        let forwarded_message_url = self
-            .bot
-            .forward_message(self.config.public_channel_id, chat_id, original_message.id)
-            .await?
-            .url()
-            .expect("forwarded message to have URL");
-
-        log::debug!("original message forwarded");
-
-        let channel_name = self
-            .bot
-            .get_chat(self.config.public_channel_id)
-            .await?
-            .title()
-            .unwrap_or("канал")
-            .to_owned();
+        let requester = self.fetch_person_details(author).await?;
+        let markup = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+            "🔑 Я открою",
+            CallbackData::HostOpenRequest(request.id).encode(),
+        )]]);
+        self.request(
+            Some(self.config.private_chat_id),
+            self.send_message_to(
+                self.config.private_chat_id,
+                self.format_open_request(&request, &requester, None),
+            )
+            .reply_markup(markup),
+        )
+        .await?;
 
-        self.send_message_reply(
-            msg,
-            format!("✔️ Запостил в <a href=\"{forwarded_message_url}\">{channel_name}</a>"),
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                "📨 Передал резидентам, напишу в личку, когда кто-то согласится (если ты мне уже писал)",
+            ),
         )
         .await?;
 
         Ok(())
     }
 
-    fn format_person_link(&self, details: &PersonDetails) -> String {
-        format!(
-            "<a href=\"{}\">{}</a>{}",
-            details.link,
-            details.display_name,
-            if details.resident { "®️" } else { "" }
-        )
-    }
+    async fn handle_ask_open_cancel(&self, msg: &Message, args: &str) -> Result<()> {
+        let author = Self::message_author(msg);
+        let (day, _) = parse_day_purpose(args, today());
+        // the host is told by the backend, the same way as when the visit is unplanned
+        if self
+            .backend()
+            .cancel_open_request(author, day)
+            .await?
+            .is_none()
+        {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!("🤷 Ты не просил открыть спейс {}", format_date(day)),
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
 
-    fn format_visit_without_status(&self, v: &Visit, details: &PersonDetails) -> String {
-        format!(
-            "{}{}",
-            self.format_person_link(details),
-            if !v.purpose.is_empty() {
-                format!(": \"{}\"", v.purpose)
-            } else {
-                "".to_owned()
-            }
-        )
+        self.acknowledge_message(msg).await?;
+
+        Ok(())
     }
 
-    async fn get_status(&self) -> Result<String> {
-        let today = today();
-        let mut visits = self.backend().get_visits(today, today).await?;
+    async fn handle_host_open_request_callback(
+        &self,
+        q: &CallbackQuery,
+        id: i64,
+    ) -> Result<String> {
+        let host = Uid(q.from.id);
+        if !self.is_resident(q.from.id).await? {
+            return Ok("❌ Нужно быть резидентом".to_owned());
+        }
+        let Some(request) = self.backend().get_open_request(id).await? else {
+            return Ok("🤷 Нет такой просьбы".to_owned());
+        };
+        if request.day < today() {
+            return Ok("Этот день уже прошёл".to_owned());
+        }
+        if !self.backend().host_open_request(&request, host).await? {
+            return Ok("Уже встречает кто-то другой".to_owned());
+        }
 
         let details = self
-            .fetch_persons_details(visits.iter().map(|v| v.person))
+            .fetch_persons_details([request.requester, host])
             .await?;
+        if let Some(msg) = q.regular_message() {
+            self.request(
+                Some(msg.chat.id),
+                self.bot
+                    .edit_message_text(
+                        msg.chat.id,
+                        msg.id,
+                        self.format_open_request(
+                            &request,
+                            &details[&request.requester],
+                            Some(&details[&host]),
+                        ),
+                    )
+                    .parse_mode(ParseMode::Html)
+                    .disable_link_preview(true),
+            )
+            .await?;
+        }
 
-        visits.sort_by_key(|v| if details[&v.person].resident { 0 } else { 1 });
-
-        let mut status = String::new();
-
-        let checked_in = visits
-            .iter()
-            .filter(|v| v.status == VisitStatus::CheckedIn)
-            .map(|v| self.format_visit_without_status(v, &details[&v.person]))
-            .join("\n");
+        self.notify_persons(
+            &[request.requester],
+            &format!(
+                "🔑 {} откроет спейс {}, до встречи!",
+                self.format_person_link(&details[&host]),
+                Self::format_open_request_time(&request)
+            ),
+        )
+        .await;
 
-        let any_resident_inside = visits
-            .iter()
-            .any(|v| v.status == VisitStatus::CheckedIn && details[&v.person].resident);
+        let toast = "🔑 Спасибо! Запланировал тебе визит".to_owned();
+        Ok(match self.closure_warning(request.day).await? {
+            Some(warning) => format!("{toast}\n\n{warning}"),
+            None => toast,
+        })
+    }
 
-        let anybody_inside = visits.iter().any(|v| v.status == VisitStatus::CheckedIn);
+    fn format_expiry(expires_at: DateTime<Utc>) -> String {
+        let expires_at = to_local(expires_at);
+        format!(
+            "{} {}",
+            format_date(expires_at.date_naive()),
+            expires_at.format("%H:%M")
+        )
+    }
 
-        if any_resident_inside {
-            status.push_str("🟢 Хакспейс сейчас открыт");
-        } else {
-            status.push_str("🔒 Хакспейс сейчас закрыт");
-            if anybody_inside {
-                status.push_str(", но кто-то из гостей внутри???");
-            }
-            status.push_str(
-                "\n\n💡 Если хочешь зайти, можно спросить в чате, возможно кто-то из резидентов может прийти.",
-            );
+    async fn handle_motd(&self, msg: &Message) -> Result<()> {
+        let text = Self::message_text(msg).trim();
+        if text.is_empty() {
+            let reply = match self.backend().get_motd().await? {
+                Some(motd) => format!(
+                    "📣 {}\n\n⏳ До {}",
+                    teloxide::utils::html::escape(&motd.text),
+                    Self::format_expiry(motd.expires_at)
+                ),
+                None => "Заметки сейчас нет".to_owned(),
+            };
+            self.request(Some(msg.chat.id), self.send_message_reply(msg, reply))
+                .await?;
+            return Ok(());
         }
 
-        if !checked_in.is_empty() {
-            status.push_str("\n\n👷 Сейчас в хакспейсе:\n");
-            status.push_str(&checked_in);
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
         }
 
-        let planned = visits
-            .iter()
-            .filter(|v| v.status == VisitStatus::Planned)
-            .map(|v| self.format_visit_without_status(v, &details[&v.person]))
-            .join("\n");
-
-        if !planned.is_empty() {
-            status.push_str("\n\n📅 Планировали зайти:\n");
-            status.push_str(&planned);
+        if text == "убрать" {
+            let reply = if self.backend().clear_motd().await? {
+                "🧹 Убрал заметку"
+            } else {
+                "Заметки и так нет"
+            };
+            self.request(Some(msg.chat.id), self.send_message_reply(msg, reply))
+                .await?;
+            return Ok(());
         }
 
-        let left = visits
-            .iter()
-            .filter(|v| v.status == VisitStatus::CheckedOut)
-            .map(|v| self.format_visit_without_status(v, &details[&v.person]))
-            .join("\n");
-
-        if !left.is_empty() {
-            status.push_str("\n\n🌆 Уже ушли:\n");
-            status.push_str(&left);
+        let (expires_at, note) = parse_expiry(text, crate::utils::now(), MOTD_DEFAULT_DURATION);
+        if note.is_empty() || note.chars().count() > MOTD_MAX_LEN {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!("❌ Нужен текст заметки, не длиннее {MOTD_MAX_LEN} символов"),
+                ),
+            )
+            .await?;
+            return Ok(());
         }
 
-        let week_visits = self
-            .backend()
-            .get_visits(today + TimeDelta::days(1), today + TimeDelta::days(7))
+        self.backend()
+            .set_motd(Motd {
+                text: note.to_owned(),
+                author: Self::message_author(msg),
+                expires_at,
+            })
             .await?;
 
-        let details = self
-            .fetch_persons_details(week_visits.iter().map(|v| v.person))
-            .await?;
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                format!("📣 Повесил заметку до {}", Self::format_expiry(expires_at)),
+            ),
+        )
+        .await?;
 
-        let formatted_week_visits = self.format_visits(week_visits, &details);
+        Ok(())
+    }
 
-        if !formatted_week_visits.is_empty() {
-            status.push_str("\n\n🗓️ Планы на неделю:\n\n");
-            status.push_str(&formatted_week_visits);
+    async fn handle_notice(&self, msg: &Message) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
         }
 
-        Ok(status)
-    }
-
-    async fn handle_status(&self, msg: &Message) -> Result<()> {
-        if msg.chat.id == self.config.public_chat_id
-            && let Some(msg_id) = self.get_status_message_id()
-        {
-            self.send_message_reply(
-                msg,
-                format!(
-                    "Посмотри в <a href=\"{}\">закрепе</a>",
-                    Message::url_of(self.config.public_chat_id, None, msg_id)
-                        .expect("should be able to create url of live status message")
+        let (expires_at, text) = parse_expiry(
+            Self::message_text(msg),
+            crate::utils::now(),
+            NOTICE_DEFAULT_DURATION,
+        );
+        if text.is_empty() || text.chars().count() > NOTICE_MAX_LEN {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!("❌ Нужен текст объявления, не длиннее {NOTICE_MAX_LEN} символов"),
                 ),
             )
             .await?;
             return Ok(());
         }
 
-        let status = self.get_status().await?;
+        self.backend()
+            .add_notice(Self::message_author(msg), text.to_owned(), expires_at)
+            .await?;
 
-        self.send_message_reply(msg, status).await?;
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                format!(
+                    "📌 Повесил объявление до {}, убрать можно в /notices",
+                    Self::format_expiry(expires_at)
+                ),
+            ),
+        )
+        .await?;
 
         Ok(())
     }
 
-    fn format_visit(&self, v: &Visit, details: &PersonDetails) -> String {
-        let status_str = match v.status {
-            VisitStatus::Planned => "",
-            VisitStatus::CheckedIn => " (сейчас в спейсе 👷)",
-            VisitStatus::CheckedOut => " (ушёл 🌆)",
+    /// Delete buttons are only shown for the notices of `viewer`
+    async fn render_notices(&self, viewer: Uid) -> Result<(String, InlineKeyboardMarkup)> {
+        let notices = self.backend().get_notices().await?;
+        if notices.is_empty() {
+            return Ok((
+                "📋 Объявлений нет".to_owned(),
+                InlineKeyboardMarkup::default(),
+            ));
+        }
+
+        let details = self
+            .fetch_persons_details(notices.iter().map(|n| n.author))
+            .await?;
+        let text = format!(
+            "📋 Объявления:\n{}",
+            notices
+                .iter()
+                .enumerate()
+                .map(|(i, n)| format!(
+                    "{}. {} — {}, до {}",
+                    i + 1,
+                    teloxide::utils::html::escape(&n.text),
+                    self.format_person_link(&details[&n.author]),
+                    Self::format_expiry(n.expires_at)
+                ))
+                .join("\n")
+        );
+
+        let markup = InlineKeyboardMarkup {
+            inline_keyboard: notices
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| n.author == viewer)
+                .map(|(i, n)| {
+                    vec![InlineKeyboardButton::callback(
+                        format!("🗑 Убрать {}", i + 1),
+                        CallbackData::DeleteNotice(n.id).encode(),
+                    )]
+                })
+                .collect(),
         };
-        format!(
-            "{}{}",
-            self.format_visit_without_status(v, details),
-            status_str
-        )
+
+        Ok((truncate_message(text), markup))
     }
 
-    fn format_day<'a>(
-        &self,
-        vs: impl IntoIterator<Item = &'a Visit>,
-        details: &HashMap<Uid, PersonDetails>,
-    ) -> String {
-        vs.into_iter()
-            .sorted_by_key(|v| if details[&v.person].resident { 0 } else { 1 })
-            .map(|v| self.format_visit(v, &details[&v.person]))
-            .join("\n")
+    async fn handle_notices(&self, msg: &Message) -> Result<()> {
+        let (text, markup) = self.render_notices(Self::message_author(msg)).await?;
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(msg, text).reply_markup(markup),
+        )
+        .await?;
+        Ok(())
     }
 
-    fn format_visits(&self, mut vs: Vec<Visit>, details: &HashMap<Uid, PersonDetails>) -> String {
-        vs.sort_by_key(|v| v.day);
+    async fn handle_delete_notice_callback(&self, q: &CallbackQuery, id: i64) -> Result<String> {
+        let person = Uid(q.from.id);
+        if !self.backend().remove_notice(id, person).await? {
+            return Ok("Это не твоё объявление или его уже нет".to_owned());
+        }
 
-        vs.chunk_by(|v1, v2| v1.day == v2.day)
-            .map(|vs| {
-                let day = vs[0].day;
-                format!("{}:\n{}", format_date(day), self.format_day(vs, details))
-            })
-            .join("\n\n")
+        if let Some(msg) = q.regular_message() {
+            let (text, markup) = self.render_notices(person).await?;
+            self.request(
+                Some(msg.chat.id),
+                self.bot
+                    .edit_message_text(msg.chat.id, msg.id, text)
+                    .parse_mode(ParseMode::Html)
+                    .disable_link_preview(true)
+                    .reply_markup(markup),
+            )
+            .await?;
+        }
+
+        Ok("🗑 Убрал объявление".to_owned())
     }
 
-    async fn handle_get_visits(&self, msg: &Message) -> Result<()> {
-        let visits = self
+    async fn handle_audit(&self, msg: &Message) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let entries = self
             .backend()
-            .get_visits(today(), today() + TimeDelta::days(185))
-            .await?;
-
-        let details = self
-            .fetch_persons_details(visits.iter().map(|v| v.person))
+            .get_audit_entries(now - AUDIT_LOG_PERIOD, now)
             .await?;
 
-        let mut formatted_visits = self.format_visits(visits, &details);
-
-        if !formatted_visits.is_empty() {
-            formatted_visits =
-                format!("🗓️ Планы посещений на ближайшие полгода:\n\n{formatted_visits}",);
+        let text = if entries.is_empty() {
+            "🧾 За неделю в журнале ничего нет".to_owned()
         } else {
-            formatted_visits = "😔 Нет никаких планов".to_owned();
-        }
+            let details = self
+                .fetch_persons_details(entries.iter().map(|e| e.person))
+                .await?;
+            let lines = entries
+                .iter()
+                .map(|e| {
+                    let at = to_local(e.at);
+                    let action = match e.action {
+                        AuditAction::LockupStarted => "получил(а) чек-лист закрытия",
+                        AuditAction::LockupConfirmed => "закрыл(а) спейс",
+                    };
+                    let mut line = format!(
+                        "• {} {} — {} {action}",
+                        format_date(at.date_naive()),
+                        at.format("%H:%M"),
+                        self.format_person_link(&details[&e.person]),
+                    );
+                    if !e.details.is_empty() {
+                        line.push_str(&format!(" ({})", teloxide::utils::html::escape(&e.details)));
+                    }
+                    line
+                })
+                .join("\n");
+            format!("🧾 Журнал за неделю:\n{lines}")
+        };
 
-        self.send_message_reply(msg, formatted_visits).await?;
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(msg, truncate_message(text)),
+        )
+        .await?;
 
         Ok(())
     }
 
-    async fn load_status_message_id(pool: &SqlitePool) -> Result<Option<MessageId>> {
-        let message_id = sqlx::query!("SELECT message_id FROM status_messages")
-            .map(|r| r.message_id)
-            .fetch_optional(pool)
-            .await?
-            .map(|id| MessageId(id as i32));
-        Ok(message_id)
-    }
+    async fn handle_open(&self, msg: &Message) -> Result<()> {
+        if !self.check_author_is_resident(msg).await? {
+            return Ok(());
+        }
+        let Some((time, note)) = parse_open(Self::message_text(msg)) else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    "❌ Напиши, до скольки открыто: /open до 23:00 [заметка]",
+                ),
+            )
+            .await?;
+            return Ok(());
+        };
 
-    async fn save_status_message_id(pool: &SqlitePool, id: Option<MessageId>) -> Result<()> {
-        let mut tx = pool.begin().await?;
-        sqlx::query!("DELETE FROM status_messages")
-            .execute(&mut *tx)
+        let now = crate::utils::now();
+        let closes_at = next_daily(&now, time);
+        if closes_at - now > OPEN_SESSION_MAX_DURATION {
+            // most likely a time that has just passed, not one tomorrow evening
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    format!(
+                        "❌ До {} больше {} часов, напиши время закрытия в ближайшие {} часов",
+                        time.format("%H:%M"),
+                        OPEN_SESSION_MAX_DURATION.num_hours(),
+                        OPEN_SESSION_MAX_DURATION.num_hours()
+                    ),
+                ),
+            )
             .await?;
-        if let Some(id) = id {
-            sqlx::query!("INSERT INTO status_messages (message_id) VALUES (?1)", id.0)
-                .execute(&mut *tx)
-                .await?;
+            return Ok(());
         }
-        tx.commit().await?;
-        Ok(())
-    }
+        let tomorrow = if closes_at.date_naive() != now.date_naive() {
+            " (после полуночи)"
+        } else {
+            ""
+        };
+        let closes_at = closes_at.with_timezone(&Utc);
 
-    async fn set_status_message_id(&self, id: Option<MessageId>) -> Result<()> {
-        Self::save_status_message_id(self.backend().pool(), id).await?;
-        *self.status_message_id.write().unwrap() = id;
-        Ok(())
-    }
+        self.backend()
+            .open_space(Self::message_author(msg), note.to_owned(), closes_at)
+            .await?;
+
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                format!(
+                    "🟢 Открыто до {}{tomorrow}\n\n⏰ Напомню в личке за {} минут до закрытия, если ты мне уже писал, а потом закрою сам",
+                    time.format("%H:%M"),
+                    OPEN_SESSION_REMINDER_BEFORE.num_minutes()
+                ),
+            ),
+        )
+        .await?;
 
-    fn get_status_message_id(&self) -> Option<MessageId> {
-        *self.status_message_id.read().unwrap()
+        Ok(())
     }
 
-    async fn handle_live_status(&self, msg: &Message) -> Result<()> {
-        let Some(chat_id) = self.check_is_public_chat_msg(msg).await? else {
+    async fn handle_close(&self, msg: &Message) -> Result<()> {
+        if self.check_is_public_chat_msg(msg).await?.is_none() {
             return Ok(());
         };
         if !self.check_author_is_resident(msg).await? {
             return Ok(());
         }
 
-        if let Some(msg_id) = self.get_status_message_id() {
-            self.bot
-                .unpin_chat_message(chat_id)
-                .message_id(msg_id)
-                .await?;
-            self.set_status_message_id(None).await?;
+        let author = Self::message_author(msg);
+        let inside = self
+            .backend()
+            .get_visits(today(), today())
+            .await?
+            .into_iter()
+            .filter(|v| v.status == VisitStatus::CheckedIn && v.person != author)
+            .map(|v| v.person)
+            .collect_vec();
+
+        self.backend().check_out_everybody().await?;
+        if let Some(session) = self.backend().get_open_session().await?
+            && session.is_open(Utc::now())
+        {
+            self.backend().close_open_session(session.id).await?;
         }
 
-        let msg_id = self
-            .send_message_public_chat(Self::get_full_live_status(&self.get_status().await?))
-            .reply_markup(Self::live_status_markup())
-            .await?
-            .id;
-        self.set_status_message_id(Some(msg_id)).await?;
+        self.acknowledge_message(msg).await?;
 
-        self.bot
-            .pin_chat_message(chat_id, msg_id)
-            .disable_notification(true)
-            .await?;
+        if !self.config.lockup_checklist.is_empty() {
+            let residents = self.residents(inside.iter().copied()).await?;
+            let guests = inside
+                .into_iter()
+                .filter(|p| !residents.contains(p))
+                .collect_vec();
+            self.send_lockup_checklist(author, &guests, true).await?;
+        }
 
         Ok(())
     }
 
-    async fn handle_unlive_status(&self, msg: &Message) -> Result<()> {
-        let Some(chat_id) = self.check_is_public_chat_msg(msg).await? else {
+    pub async fn announce_plan(&self, visit_update: &VisitUpdate) -> Result<()> {
+        let day = visit_update.day;
+        self.post_announcement(
+            format!(
+                "🗓️🚋 {}{} планирует зайти в хакспейс {}{}",
+                self.format_person_link(&self.fetch_person_details(visit_update.person).await?),
+                self.format_actor(visit_update.person, day).await?,
+                format_date(day),
+                visit_update
+                    .purpose
+                    .as_deref()
+                    .map(|p| { format!(": \"{p}\"") })
+                    .unwrap_or_default()
+            ),
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![vec![
+                    InlineKeyboardButton::callback(
+                        format!(
+                            "🚋 Я тоже зайду {}",
+                            format_close_date(day).unwrap_or("в этот день")
+                        ),
+                        CallbackData::PlanVisit(Some(day)).encode(),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "🤔 Или нет",
+                        CallbackData::UnplanVisit(Some(day)).encode(),
+                    ),
+                ]],
+            },
+        )
+        .await
+    }
+
+    /// Tells the host, offering to drop their own plan if they only came for this guest
+    pub async fn announce_open_request_withdrawn(&self, request: &OpenRequest) -> Result<()> {
+        let Some(host) = request.host else {
             return Ok(());
         };
-        if !self.check_author_is_resident(msg).await? {
+        let requester = self.fetch_person_details(request.requester).await?;
+        let text = format!(
+            "🙅 {} больше не просит открыть спейс {}",
+            self.format_person_link(&requester),
+            Self::format_open_request_time(request)
+        );
+
+        let still_hosting = self
+            .backend()
+            .get_open_requests(request.day, request.day)
+            .await?
+            .iter()
+            .any(|r| r.host == Some(host));
+        let host_plan = self.backend().get_visit(host, request.day).await?;
+        if still_hosting
+            || !host_plan.is_some_and(|v| {
+                v.status == VisitStatus::Planned && v.purpose == HOST_VISIT_PURPOSE
+            })
+        {
+            self.notify_persons(&[host], &text).await;
             return Ok(());
         }
 
-        if let Some(msg_id) = self.get_status_message_id() {
-            self.bot
-                .unpin_chat_message(chat_id)
-                .message_id(msg_id)
-                .await?;
-            self.set_status_message_id(None).await?;
+        let chat_id = ChatId::from(host.0);
+        let markup = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+            "🤔 Тогда не приду",
+            CallbackData::UnplanVisit(Some(request.day)).encode(),
+        )]]);
+        if let Err(e) = self
+            .background_request(
+                Some(chat_id),
+                self.send_message_to(chat_id, format!("{text}\n\nТвой визит пока в планах"))
+                    .reply_markup(markup),
+            )
+            .await
+        {
+            log::warn!("Failed to notify {:?}: {e}", host);
         }
         Ok(())
     }
 
-    fn get_full_live_status(live_status: &str) -> String {
-        live_status.to_owned()
-            + "\n\nОбновлено: "
-            + &crate::utils::now()
-                .format_localized("%c %Z", Locale::ru_RU)
-                .to_string()
+    pub async fn announce_unplan(&self, person: Uid, day: NaiveDate) -> Result<()> {
+        self.post_announcement(
+            format!(
+                "🗓️🤔 {} больше не планирует зайти в хакспейс {}",
+                self.format_person_link(&self.fetch_person_details(person).await?),
+                format_date(day)
+            ),
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![vec![
+                    InlineKeyboardButton::callback(
+                        format!(
+                            "🤔 Я тоже не приду {}",
+                            format_close_date(day).unwrap_or("в этот день")
+                        ),
+                        CallbackData::UnplanVisit(Some(day)).encode(),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "🚋 Или приду",
+                        CallbackData::PlanVisit(Some(day)).encode(),
+                    ),
+                ]],
+            },
+        )
+        .await
     }
 
-    async fn update_live_status_message(&self, live_status: &str) -> Result<()> {
-        let Some(msg_id) = self.get_status_message_id() else {
+    async fn post_announcement(&self, text: String, markup: InlineKeyboardMarkup) -> Result<()> {
+        if !self.config.aggregate_announcements {
+            let msg = self
+                .request(
+                    Some(self.config.public_chat_id),
+                    self.send_message_public_chat(text).reply_markup(markup),
+                )
+                .await?;
+            self.track_public_chat_message(&msg);
             return Ok(());
+        }
+
+        let day = today();
+        let pool = self.backend().pool().clone();
+        let existing = Self::load_daily_announcement(&pool, day).await?;
+
+        let line = format!("{} {}", crate::utils::now().format("%H:%M"), text);
+        let mut lines = match existing {
+            Some((_, ref lines)) => lines.lines().collect_vec(),
+            None => Vec::new(),
         };
+        let skip = (lines.len() + 1).saturating_sub(DAILY_ANNOUNCEMENT_MAX_LINES);
+        lines.drain(..skip);
+        lines.push(&line);
+        let header = format!("🗓️ Сегодня в хакспейсе, {}:\n\n", format_date(day));
+        // the oldest lines go first, an overlong message would fail on every retry
+        let text_length = |lines: &[&str]| {
+            header.encode_utf16().count()
+                + lines
+                    .iter()
+                    .map(|l| l.encode_utf16().count() + 1)
+                    .sum::<usize>()
+        };
+        while lines.len() > 1 && text_length(&lines) > MESSAGE_LENGTH_LIMIT {
+            lines.remove(0);
+        }
+        let lines = lines.join("\n");
+        let full_text = truncate_message(format!("{header}{lines}"));
+
+        // unknown after a restart, then the message may well have scrolled away
+        let last_message_id = self.last_public_chat_message_id.load(Ordering::Relaxed);
+        if let Some((message_id, _)) = existing
+            && last_message_id != 0
+            && last_message_id - message_id.0 <= DAILY_ANNOUNCEMENT_SCROLL_LIMIT
+        {
+            let edited = self
+                .request(
+                    Some(self.config.public_chat_id),
+                    self.bot
+                        .edit_message_text(self.config.public_chat_id, message_id, &full_text)
+                        .parse_mode(ParseMode::Html)
+                        .disable_link_preview(true)
+                        .reply_markup(Self::live_status_markup()),
+                )
+                .await;
+            match edited {
+                Ok(_) => {
+                    Self::save_daily_announcement(&pool, day, message_id, &lines).await?;
+                    return Ok(());
+                }
+                Err(e) => log::warn!("Failed to edit daily announcement, posting a new one: {e:?}"),
+            }
+        }
 
-        self.bot
-            .edit_message_text(
-                self.config.public_chat_id,
-                msg_id,
-                Self::get_full_live_status(live_status),
+        let msg = self
+            .request(
+                Some(self.config.public_chat_id),
+                self.send_message_public_chat(full_text)
+                    .reply_markup(Self::live_status_markup()),
             )
-            .parse_mode(ParseMode::Html)
-            .disable_link_preview(true)
-            .reply_markup(Self::live_status_markup())
             .await?;
+        self.track_public_chat_message(&msg);
+        Self::save_daily_announcement(&pool, day, msg.id, &lines).await?;
 
         Ok(())
     }
 
-    fn message_text(msg: &Message) -> &str {
-        strip_command(msg.text().expect("message to have text"))
+    async fn load_daily_announcement(
+        pool: &SqlitePool,
+        day: NaiveDate,
+    ) -> Result<Option<(MessageId, String)>> {
+        let day = day.num_days_from_ce();
+        Ok(sqlx::query!(
+            "SELECT message_id, text FROM daily_announcements WHERE day = ?1",
+            day
+        )
+        .map(|r| (MessageId(r.message_id as i32), r.text))
+        .fetch_optional(pool)
+        .await?)
     }
 
-    fn message_author(msg: &Message) -> Uid {
-        Uid(msg.from.as_ref().expect("message to have author").id)
+    async fn save_daily_announcement(
+        pool: &SqlitePool,
+        day: NaiveDate,
+        message_id: MessageId,
+        text: &str,
+    ) -> Result<()> {
+        let day = day.num_days_from_ce();
+        let mut tx = pool.begin().await?;
+        sqlx::query!("DELETE FROM daily_announcements WHERE day < ?1", day)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO daily_announcements (day, message_id, text) VALUES (?1, ?2, ?3)
+            ON CONFLICT (day) DO UPDATE SET message_id = ?2, text = ?3",
+            day,
+            message_id.0,
+            text,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
     }
 
-    fn parse_visit_message(msg: &Message) -> VisitUpdate {
-        parse_visit_text(Self::message_author(msg), Self::message_text(msg))
+    fn track_public_chat_message(&self, msg: &Message) {
+        if msg.chat.id == self.config.public_chat_id {
+            self.last_public_chat_message_id
+                .fetch_max(msg.id.0, Ordering::Relaxed);
+            if let Some(user) = &msg.from
+                && let Some(username) = &user.username
+            {
+                self.known_usernames
+                    .write()
+                    .unwrap()
+                    .insert(username.to_lowercase(), user.id);
+            }
+        }
     }
 
-    fn common_modifiers(send_message: JsonRequest<SendMessage>) -> JsonRequest<SendMessage> {
-        send_message
-            .disable_notification(true)
-            .parse_mode(ParseMode::Html)
-            .disable_link_preview(true)
-    }
+    async fn handle_calendar(&self, msg: &Message) -> Result<()> {
+        if !msg.chat.is_private() {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Нужно написать мне в личку"),
+            )
+            .await?;
+            return Ok(());
+        }
+        let Some(base_url) = &self.config.public_api_url else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "🤷 Календарь пока не настроен"),
+            )
+            .await?;
+            return Ok(());
+        };
+        let base_url = base_url.trim_end_matches('/');
 
-    fn send_message_public_chat(&self, text: impl Into<String>) -> JsonRequest<SendMessage> {
-        Self::common_modifiers(self.bot.send_message(self.config.public_chat_id, text))
-    }
+        let person = Self::message_author(msg);
+        let token = if Self::message_text(msg).trim() == "сбросить" {
+            self.backend().reset_feed_token(person).await?
+        } else {
+            self.backend().get_feed_token(person).await?
+        };
 
-    fn send_message_reply(
-        &self,
-        msg: &Message,
-        text: impl Into<String>,
-    ) -> JsonRequest<SendMessage> {
-        Self::common_modifiers(
-            self.bot
-                .send_message(msg.chat.id, text)
-                .with_payload_mut(|p| p.message_thread_id = msg.thread_id)
-                .reply_to(msg.id),
+        self.request(
+            Some(msg.chat.id),
+            self.send_message_reply(
+                msg,
+                format!(
+                    "📅 Расписание спейса: {base_url}/calendar.ics\n\
+                    🔒 Твои планы и события: {base_url}/my_calendar.ics?token={token}\n\n\
+                    Добавь ссылку как подписку в календаре. Личную ссылку никому не показывай, \
+                    если она утекла — \"/calendar сбросить\""
+                ),
+            ),
         )
-    }
+        .await?;
 
-    async fn acknowledge_message(&self, msg: &Message) -> Result<()> {
-        self.bot
-            .set_message_reaction(msg.chat.id, msg.id)
-            .reaction(vec![ReactionType::Emoji {
-                emoji: "✍".to_owned(),
-            }])
-            .await?;
         Ok(())
     }
 
-    async fn handle_plan_visit(&self, msg: &Message) -> Result<()> {
-        let visit_update = Self::parse_visit_message(msg);
+    async fn handle_subscribe(&self, msg: &Message) -> Result<()> {
+        if !msg.chat.is_private() {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(msg, "❌ Нужно написать мне в личку"),
+            )
+            .await?;
+            return Ok(());
+        }
 
-        self.backend()
-            .plan_visit(visit_update.person, visit_update.day, visit_update.purpose)
+        let Some(subscription) =
+            parse_subscription(Self::message_author(msg), Self::message_text(msg))
+        else {
+            self.request(
+                Some(msg.chat.id),
+                self.send_message_reply(
+                    msg,
+                    "❌ Не понял. Пример: <code>/subscribe закрытие 23:00-09:00</code>",
+                ),
+            )
             .await?;
+            return Ok(());
+        };
+
+        self.backend().subscribe(subscription).await?;
 
         self.acknowledge_message(msg).await?;
 
         Ok(())
     }
 
-    async fn handle_unplan_visit(&self, msg: &Message) -> Result<()> {
-        let visit_update = Self::parse_visit_message(msg);
-
+    async fn handle_unsubscribe(&self, msg: &Message) -> Result<()> {
         self.backend()
-            .unplan_visit(visit_update.person, visit_update.day)
+            .unsubscribe(Self::message_author(msg))
             .await?;
 
         self.acknowledge_message(msg).await?;
@@ -786,142 +4255,311 @@ impl<B: Backend> TelegramBot<B> {
         Ok(())
     }
 
-    async fn handle_check_in(&self, msg: &Message) -> Result<()> {
-        let person = Self::message_author(msg);
-        let purpose_raw = Self::message_text(msg);
-        let purpose = if purpose_raw.is_empty() {
-            None
-        } else {
-            Some(purpose_raw.to_owned())
-        };
-
-        self.backend().check_in(person, purpose).await?;
+    async fn announce_open_state(&self, open: bool) {
+        if let Err(e) = self.notify_subscribers(open).await {
+            log::error!("Error notifying subscribers: {:?}", e);
+        }
+        if let Err(e) = self.post_open_state_to_channel(open).await {
+            log::error!("Error posting open state to channel: {:?}", e);
+        }
+    }
 
-        self.acknowledge_message(msg).await?;
+    async fn post_open_state_to_channel(&self, open: bool) -> Result<()> {
+        let now = crate::utils::now();
+        let pool = self.backend().pool().clone();
+        if open {
+            let msg_id = self
+                .background_request(
+                    Some(self.config.public_channel_id),
+                    self.bot.send_message(
+                        self.config.public_channel_id,
+                        format!("🟢 Открыто с {}", now.format("%H:%M")),
+                    ),
+                )
+                .await?
+                .id;
+            Self::save_channel_open_message(&pool, msg_id, now.with_timezone(&Utc)).await?;
+            return Ok(());
+        }
 
+        if let Some((msg_id, opened_at)) = Self::take_channel_open_message(&pool).await? {
+            self.background_request(
+                Some(self.config.public_channel_id),
+                self.bot.edit_message_text(
+                    self.config.public_channel_id,
+                    msg_id,
+                    format!(
+                        "🔒 Закрыто (было открыто {}–{})",
+                        to_local(opened_at).format("%H:%M"),
+                        now.format("%H:%M")
+                    ),
+                ),
+            )
+            .await?;
+        } else {
+            self.background_request(
+                Some(self.config.public_channel_id),
+                self.bot
+                    .send_message(self.config.public_channel_id, "🔒 Закрыто"),
+            )
+            .await?;
+        }
         Ok(())
     }
 
-    pub async fn announce_check_in(&self, visit_update: &VisitUpdate) -> Result<()> {
-        self.send_message_public_chat(format!(
-            "👷 {} пришёл в хакспейс{}",
-            self.format_person_link(&self.fetch_person_details(visit_update.person).await?),
-            visit_update
-                .purpose
-                .as_deref()
-                .map(|p| { format!(": \"{p}\"") })
-                .unwrap_or_default()
-        ))
-        .reply_markup(InlineKeyboardMarkup {
-            inline_keyboard: vec![vec![
-                InlineKeyboardButton::callback("👷 Я тоже в спейсе", "/checkin"),
-                InlineKeyboardButton::callback("🌆 А я уже ушёл", "/checkout"),
-            ]],
-        })
+    async fn save_channel_open_message(
+        pool: &SqlitePool,
+        message_id: MessageId,
+        opened_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let opened_at = opened_at.timestamp();
+        sqlx::query!(
+            "INSERT INTO channel_open_message (id, message_id, opened_at) VALUES (0, ?1, ?2)
+            ON CONFLICT (id) DO UPDATE SET message_id = ?1, opened_at = ?2",
+            message_id.0,
+            opened_at,
+        )
+        .execute(pool)
         .await?;
         Ok(())
     }
 
-    async fn handle_check_out(&self, msg: &Message) -> Result<()> {
-        let person = Self::message_author(msg);
-
-        self.backend().check_out(person).await?;
-
-        self.acknowledge_message(msg).await?;
+    async fn take_channel_open_message(
+        pool: &SqlitePool,
+    ) -> Result<Option<(MessageId, DateTime<Utc>)>> {
+        Ok(
+            sqlx::query!("DELETE FROM channel_open_message RETURNING message_id, opened_at")
+                .map(|r| {
+                    (
+                        MessageId(r.message_id as i32),
+                        DateTime::from_timestamp(r.opened_at, 0).unwrap_or_default(),
+                    )
+                })
+                .fetch_optional(pool)
+                .await?,
+        )
+    }
 
+    async fn notify_subscribers(&self, open: bool) -> Result<()> {
+        let text = if open {
+            "🟢 Хакспейс открылся, кто-то из резидентов уже внутри"
+        } else {
+            "🔒 Хакспейс закрылся"
+        };
+        let time = crate::utils::now().time();
+        for subscription in self.backend().get_subscriptions().await? {
+            if !open && !subscription.notify_close {
+                continue;
+            }
+            if subscription.quiet_hours.is_some_and(|q| q.contains(time)) {
+                continue;
+            }
+            if let Err(e) = self
+                .background_request(
+                    Some(subscription.person.0.into()),
+                    self.bot.send_message(subscription.person.0, text),
+                )
+                .await
+            {
+                log::warn!(
+                    "Failed to notify subscriber {:?}: {:?}",
+                    subscription.person,
+                    e
+                );
+            }
+        }
         Ok(())
     }
 
-    async fn handle_close(&self, msg: &Message) -> Result<()> {
-        if self.check_is_public_chat_msg(msg).await?.is_none() {
-            return Ok(());
+    /// Returns a toast to show to the user who pressed the button
+    async fn handle_callback(self: Arc<Self>, q: &CallbackQuery) -> Result<Option<String>> {
+        let Some(data) = q.data.as_deref() else {
+            return Ok(None);
         };
-        if !self.check_author_is_resident(msg).await? {
-            return Ok(());
-        }
 
-        self.backend().check_out_everybody().await?;
+        let Some(callback_data) = CallbackData::decode(data) else {
+            log::info!("Unknown callback data: {:?}", q);
+            return Ok(Some(
+                "🤷 Эта кнопка устарела, попробуй команду или кнопку посвежее".to_owned(),
+            ));
+        };
 
-        self.acknowledge_message(msg).await?;
+        let author = Uid(q.from.id);
 
-        Ok(())
-    }
+        let toast = match callback_data {
+            CallbackData::PlanVisit(day) => {
+                let day = day.unwrap_or_else(today);
+                let warning = self.closure_warning(day).await?;
+                if let Some(warning) = warning.clone()
+                    && !self.is_resident(author.0).await?
+                {
+                    return Ok(Some(warning));
+                }
+                let previous = self.backend().get_visit(author, day).await?;
+                if self.backend().plan_visit(author, day, None).await? {
+                    let toast = format!("🗓️ Записал тебя на {}", format_date(day));
+                    self.offer_undo(&toast, author, day, previous).await?;
+                    match warning {
+                        Some(warning) => format!("{toast}\n\n{warning}"),
+                        None => toast,
+                    }
+                } else {
+                    format!("Ты уже записан на {}", format_date(day))
+                }
+            }
+            CallbackData::UnplanVisit(day) => {
+                let day = day.unwrap_or_else(today);
+                if self.backend().unplan_visit(author, day).await? {
+                    format!("🤔 Вычеркнул тебя на {}", format_date(day))
+                } else {
+                    format!("У тебя и так не было планов на {}", format_date(day))
+                }
+            }
+            CallbackData::CheckIn => {
+                let previous = self.backend().get_visit(author, today()).await?;
+                if self.backend().check_in(author, None).await? {
+                    let toast = "👷 Отметил, что ты в спейсе".to_owned();
+                    self.offer_undo(&toast, author, today(), previous).await?;
+                    toast
+                } else {
+                    "Ты уже отмечен".to_owned()
+                }
+            }
+            CallbackData::CheckOut => {
+                let current = self.backend().get_visit(author, today()).await?;
+                match current.map(|v| v.status) {
+                    Some(VisitStatus::CheckedIn) => {
+                        self.backend().check_out(author).await?;
+                        self.check_last_resident_out(author).await?;
+                        "🌆 Отметил, что ты ушёл".to_owned()
+                    }
+                    Some(VisitStatus::Planned) => {
+                        "Ты ещё не отмечен в спейсе, визит на сегодня остался в планах".to_owned()
+                    }
+                    Some(VisitStatus::CheckedOut) => "Ты уже ушёл".to_owned(),
+                    None => "Ты и так не отмечен в спейсе".to_owned(),
+                }
+            }
+            CallbackData::GetVisits(ref query) => {
+                self.handle_get_visits_callback(q, query).await?;
+                return Ok(None);
+            }
+            CallbackData::MeUnplan { person, day } => {
+                self.handle_me_unplan_callback(q, person, day).await?
+            }
+            CallbackData::Rsvp { event, going } => {
+                self.handle_rsvp_callback(q, event, going).await?
+            }
+            CallbackData::Lockup { lockup, item } => {
+                self.handle_lockup_callback(q, lockup, item).await?
+            }
+            CallbackData::HostOpenRequest(id) => {
+                self.handle_host_open_request_callback(q, id).await?
+            }
+            CallbackData::OpenSession { session, extend } => {
+                self.handle_open_session_callback(q, session, extend)
+                    .await?
+            }
+            CallbackData::DeleteNotice(id) => self.handle_delete_notice_callback(q, id).await?,
+            CallbackData::Undo(id) => self.handle_undo(q, id).await?,
+        };
 
-    pub async fn announce_plan(&self, visit_update: &VisitUpdate) -> Result<()> {
-        let day = visit_update.day;
-        self.send_message_public_chat(format!(
-            "🗓️🚋 {} планирует зайти в хакспейс {}{}",
-            self.format_person_link(&self.fetch_person_details(visit_update.person).await?),
-            format_date(day),
-            visit_update
-                .purpose
-                .as_deref()
-                .map(|p| { format!(": \"{p}\"") })
-                .unwrap_or_default()
-        ))
-        .reply_markup(InlineKeyboardMarkup {
-            inline_keyboard: vec![vec![
-                InlineKeyboardButton::callback(
-                    format!(
-                        "🚋 Я тоже зайду {}",
-                        format_close_date(day).unwrap_or("в этот день")
-                    ),
-                    format!("/planvisit {}", day),
-                ),
-                InlineKeyboardButton::callback("🤔 Или нет", format!("/unplanvisit {}", day)),
-            ]],
-        })
-        .await?;
-        Ok(())
+        Ok(Some(toast))
     }
 
-    pub async fn announce_unplan(&self, person: Uid, day: NaiveDate) -> Result<()> {
-        self.send_message_public_chat(format!(
-            "🗓️🤔 {} больше не планирует зайти в хакспейс {}",
-            self.format_person_link(&self.fetch_person_details(person).await?),
-            format_date(day)
-        ))
-        .reply_markup(InlineKeyboardMarkup {
-            inline_keyboard: vec![vec![
-                InlineKeyboardButton::callback(
-                    format!(
-                        "🤔 Я тоже не приду {}",
-                        format_close_date(day).unwrap_or("в этот день")
-                    ),
-                    format!("/unplanvisit {}", day),
-                ),
-                InlineKeyboardButton::callback("🚋 Или приду", format!("/planvisit {}", day)),
-            ]],
-        })
-        .await?;
+    /// Sends a short-lived DM with a button reverting the change the user has just made,
+    /// so button presses don't flood the chat
+    async fn offer_undo(
+        self: &Arc<Self>,
+        text: &str,
+        person: Uid,
+        day: NaiveDate,
+        previous: Option<Visit>,
+    ) -> Result<()> {
+        let id = self.next_undo_id.fetch_add(1, Ordering::Relaxed);
+        let chat_id = ChatId::from(person.0);
+        let sent = self
+            .request(
+                Some(chat_id),
+                self.send_message_to(chat_id, text)
+                    .reply_markup(InlineKeyboardMarkup {
+                        inline_keyboard: vec![vec![InlineKeyboardButton::callback(
+                            "↩️ Отменить",
+                            CallbackData::Undo(id).encode(),
+                        )]],
+                    }),
+            )
+            .await;
+        let undo_message = match sent {
+            Ok(msg) => msg,
+            // People who never started a DM with the bot just don't get the button
+            Err(e) => {
+                log::info!("Can't offer undo to {:?}: {e}", person);
+                return Ok(());
+            }
+        };
+        self.undo_actions.lock().unwrap().insert(
+            id,
+            UndoAction {
+                person,
+                day,
+                previous,
+            },
+        );
+
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(UNDO_TIMEOUT).await;
+            // Already deleted if the undo button was used
+            if self_clone
+                .undo_actions
+                .lock()
+                .unwrap()
+                .remove(&id)
+                .is_none()
+            {
+                return;
+            }
+            if let Err(e) = self_clone
+                .background_request(
+                    Some(undo_message.chat.id),
+                    self_clone
+                        .bot
+                        .delete_message(undo_message.chat.id, undo_message.id),
+                )
+                .await
+            {
+                log::warn!("Failed to delete undo message: {e}");
+            }
+        });
+
         Ok(())
     }
 
-    async fn handle_callback(&self, q: &CallbackQuery) -> Result<()> {
-        let Some(data) = q.data.as_deref() else {
-            return Ok(());
+    async fn handle_undo(&self, q: &CallbackQuery, id: u64) -> Result<String> {
+        let action = {
+            let mut undo_actions = self.undo_actions.lock().unwrap();
+            match undo_actions.get(&id) {
+                None => return Ok("Отменить уже нельзя".to_owned()),
+                Some(action) if action.person != Uid(q.from.id) => {
+                    return Ok("Это не твоя кнопка".to_owned());
+                }
+                Some(_) => undo_actions.remove(&id).unwrap(),
+            }
         };
 
-        let author = Uid(q.from.id);
+        self.backend()
+            .restore_visit(action.person, action.day, action.previous)
+            .await?;
 
-        if data.starts_with("/planvisit") {
-            let visit_update = parse_visit_text(author, strip_command(data));
-            self.backend()
-                .plan_visit(visit_update.person, visit_update.day, visit_update.purpose)
-                .await?;
-        } else if data.starts_with("/unplanvisit") {
-            let visit_update = parse_visit_text(author, strip_command(data));
-            self.backend()
-                .unplan_visit(visit_update.person, visit_update.day)
-                .await?;
-        } else if data == "/checkin" {
-            self.backend().check_in(author, None).await?;
-        } else if data == "/checkout" {
-            self.backend().check_out(author).await?;
-        } else {
-            anyhow::bail!("unhandled callback query: {:?}", q);
+        if let Some(msg) = q.regular_message() {
+            self.request(
+                Some(msg.chat.id),
+                self.bot.delete_message(msg.chat.id, msg.id),
+            )
+            .await?;
         }
 
-        Ok(())
+        Ok("↩️ Отменил".to_owned())
     }
 }
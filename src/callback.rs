@@ -0,0 +1,246 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::backend::Uid;
+
+/// Bumped whenever the encoding changes, older versions are still decoded
+const CURRENT_VERSION: &str = "1";
+const SEPARATOR: char = ':';
+/// Telegram limit for `callback_data`, in bytes
+const CALLBACK_DATA_LIMIT: usize = 64;
+/// Longest purpose filter that still fits into the data of any page button, in bytes
+const PURPOSE_FILTER_MAX_LEN: usize = CALLBACK_DATA_LIMIT - "1:gv:w:4294967295:k".len();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitsView {
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VisitsFilter {
+    All,
+    Residents,
+    Person(Uid),
+    Purpose(String),
+}
+
+impl VisitsFilter {
+    /// Cuts the keyword so every page shows the same filter as the first one
+    pub fn purpose(keyword: &str) -> Self {
+        let mut end = keyword.len().min(PURPOSE_FILTER_MAX_LEN);
+        while !keyword.is_char_boundary(end) {
+            end -= 1;
+        }
+        VisitsFilter::Purpose(keyword[..end].trim_end().to_owned())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisitsQuery {
+    pub view: VisitsView,
+    pub page: u32,
+    pub filter: VisitsFilter,
+}
+
+/// Data attached to inline keyboard buttons
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallbackData {
+    CheckIn,
+    CheckOut,
+    /// `None` means the day the button is pressed
+    PlanVisit(Option<NaiveDate>),
+    UnplanVisit(Option<NaiveDate>),
+    GetVisits(VisitsQuery),
+    /// Cancel button in `/me`, which is re-rendered for `person` afterwards
+    MeUnplan {
+        person: Uid,
+        day: NaiveDate,
+    },
+    /// Sign up for or leave an event
+    Rsvp {
+        event: i64,
+        going: bool,
+    },
+    /// Lock-up checklist buttons: toggle an item, `None` confirms the whole list
+    Lockup {
+        lockup: i64,
+        item: Option<u8>,
+    },
+    /// Resident volunteering to open the space for an /askopen request
+    HostOpenRequest(i64),
+    /// Reminder buttons of an open session: add an hour or close right away
+    OpenSession {
+        session: i64,
+        extend: bool,
+    },
+    /// Delete button the author of a notice sees in /notices
+    DeleteNotice(i64),
+    /// Id of an undo action kept in memory by the bot
+    Undo(u64),
+}
+
+fn encode_day(day: Option<NaiveDate>) -> String {
+    day.map(|d| d.num_days_from_ce().to_string())
+        .unwrap_or_default()
+}
+
+fn decode_day(day: Option<&str>) -> Option<Option<NaiveDate>> {
+    match day {
+        None | Some("") => Some(None),
+        Some(day) => Some(Some(NaiveDate::from_num_days_from_ce_opt(
+            day.parse().ok()?,
+        )?)),
+    }
+}
+
+fn decode_visits_query(args: &str, separator: char) -> Option<VisitsQuery> {
+    let mut parts = args.splitn(3, separator);
+    let view = match parts.next()? {
+        "w" => VisitsView::Week,
+        "m" => VisitsView::Month,
+        _ => return None,
+    };
+    let page = parts.next()?.parse().ok()?;
+    let filter = match parts.next()?.split_at_checked(1)? {
+        ("a", _) => VisitsFilter::All,
+        ("r", _) => VisitsFilter::Residents,
+        ("p", person) => VisitsFilter::Person(Uid::from(person.parse::<i64>().ok()?)),
+        ("k", keyword) => VisitsFilter::Purpose(keyword.to_owned()),
+        _ => return None,
+    };
+    Some(VisitsQuery { view, page, filter })
+}
+
+impl CallbackData {
+    pub fn encode(&self) -> String {
+        let mut data = match self {
+            CallbackData::CheckIn => "ci".to_owned(),
+            CallbackData::CheckOut => "co".to_owned(),
+            CallbackData::PlanVisit(day) => format!("pv:{}", encode_day(*day)),
+            CallbackData::UnplanVisit(day) => format!("uv:{}", encode_day(*day)),
+            CallbackData::GetVisits(query) => {
+                let view = match query.view {
+                    VisitsView::Week => "w",
+                    VisitsView::Month => "m",
+                };
+                let filter = match query.filter {
+                    VisitsFilter::All => "a".to_owned(),
+                    VisitsFilter::Residents => "r".to_owned(),
+                    VisitsFilter::Person(person) => format!("p{}", i64::from(person)),
+                    VisitsFilter::Purpose(ref keyword) => format!("k{keyword}"),
+                };
+                format!("gv:{view}:{}:{filter}", query.page)
+            }
+            CallbackData::MeUnplan { person, day } => {
+                format!("mu:{}:{}", i64::from(*person), encode_day(Some(*day)))
+            }
+            CallbackData::Rsvp { event, going } => format!("ev:{event}:{}", u8::from(*going)),
+            CallbackData::Lockup { lockup, item } => format!(
+                "lc:{lockup}:{}",
+                item.map(|i| i.to_string()).unwrap_or_default()
+            ),
+            CallbackData::HostOpenRequest(id) => format!("ho:{id}"),
+            CallbackData::OpenSession { session, extend } => {
+                format!("os:{session}:{}", u8::from(*extend))
+            }
+            CallbackData::DeleteNotice(id) => format!("nd:{id}"),
+            CallbackData::Undo(id) => format!("un:{id}"),
+        };
+        data.insert(0, SEPARATOR);
+        data.insert_str(0, CURRENT_VERSION);
+        debug_assert!(
+            data.len() <= CALLBACK_DATA_LIMIT,
+            "callback data too long: {data:?}"
+        );
+        data
+    }
+
+    /// Returns `None` for data this version of the bot doesn't understand
+    pub fn decode(data: &str) -> Option<Self> {
+        if data.starts_with('/') {
+            return Self::decode_v0(data);
+        }
+        let (version, data) = data.split_once(SEPARATOR)?;
+        match version {
+            "1" => Self::decode_v1(data),
+            _ => None,
+        }
+    }
+
+    fn decode_v1(data: &str) -> Option<Self> {
+        let (tag, args) = data.split_once(SEPARATOR).unwrap_or((data, ""));
+        match tag {
+            "ci" => Some(CallbackData::CheckIn),
+            "co" => Some(CallbackData::CheckOut),
+            "pv" => Some(CallbackData::PlanVisit(decode_day(Some(args))?)),
+            "uv" => Some(CallbackData::UnplanVisit(decode_day(Some(args))?)),
+            "gv" => Some(CallbackData::GetVisits(decode_visits_query(
+                args, SEPARATOR,
+            )?)),
+            "mu" => {
+                let (person, day) = args.split_once(SEPARATOR)?;
+                Some(CallbackData::MeUnplan {
+                    person: Uid::from(person.parse::<i64>().ok()?),
+                    day: decode_day(Some(day))??,
+                })
+            }
+            "ev" => {
+                let (event, going) = args.split_once(SEPARATOR)?;
+                Some(CallbackData::Rsvp {
+                    event: event.parse().ok()?,
+                    going: match going {
+                        "0" => false,
+                        "1" => true,
+                        _ => return None,
+                    },
+                })
+            }
+            "lc" => {
+                let (lockup, item) = args.split_once(SEPARATOR)?;
+                Some(CallbackData::Lockup {
+                    lockup: lockup.parse().ok()?,
+                    item: match item {
+                        "" => None,
+                        item => Some(item.parse().ok()?),
+                    },
+                })
+            }
+            "ho" => Some(CallbackData::HostOpenRequest(args.parse().ok()?)),
+            "os" => {
+                let (session, extend) = args.split_once(SEPARATOR)?;
+                Some(CallbackData::OpenSession {
+                    session: session.parse().ok()?,
+                    extend: match extend {
+                        "0" => false,
+                        "1" => true,
+                        _ => return None,
+                    },
+                })
+            }
+            "nd" => Some(CallbackData::DeleteNotice(args.parse().ok()?)),
+            "un" => Some(CallbackData::Undo(args.parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Commands with an optional day that buttons used before versioning, e.g. `/planvisit 2025-10-10`
+    fn decode_v0(data: &str) -> Option<Self> {
+        if let Some(args) = data.strip_prefix("/getvisits ") {
+            return Some(CallbackData::GetVisits(decode_visits_query(args, ' ')?));
+        }
+        let (command, day) = match data.split_once(' ') {
+            Some((command, day)) => (
+                command,
+                Some(NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d").ok()?),
+            ),
+            None => (data, None),
+        };
+        match command {
+            "/checkin" => Some(CallbackData::CheckIn),
+            "/checkout" => Some(CallbackData::CheckOut),
+            "/planvisit" => Some(CallbackData::PlanVisit(day)),
+            "/unplanvisit" => Some(CallbackData::UnplanVisit(day)),
+            _ => None,
+        }
+    }
+}
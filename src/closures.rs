@@ -0,0 +1,90 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use sqlx::sqlite::SqlitePool;
+
+/// Day the space is closed on, e.g. for renovation or holidays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Closure {
+    pub day: NaiveDate,
+    pub reason: String,
+    pub author: Uid,
+}
+
+#[derive(Debug, Clone)]
+pub struct Closures {
+    pool: SqlitePool,
+}
+
+impl Closures {
+    pub fn new(pool: SqlitePool) -> Result<Closures> {
+        Ok(Closures { pool })
+    }
+
+    /// Marks every day from `from` to `to` inclusive, replacing reasons of already closed days.
+    /// Returns the days that weren't closed before.
+    pub async fn add(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        reason: &str,
+        author: Uid,
+    ) -> Result<Vec<NaiveDate>> {
+        let author: i64 = author.into();
+        let mut added = Vec::new();
+        let mut tx = self.pool.begin().await?;
+        for day in from.iter_days().take_while(|d| *d <= to) {
+            let day_int = day.num_days_from_ce();
+            let existed = sqlx::query_scalar!("SELECT day FROM closures WHERE day = ?1", day_int)
+                .fetch_optional(&mut *tx)
+                .await?
+                .is_some();
+            sqlx::query!(
+                "INSERT INTO closures (day, reason, author) VALUES (?1, ?2, ?3)
+                ON CONFLICT (day) DO UPDATE SET reason = excluded.reason, author = excluded.author",
+                day_int,
+                reason,
+                author,
+            )
+            .execute(&mut *tx)
+            .await?;
+            if !existed {
+                added.push(day);
+            }
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Returns the number of days that were closed
+    pub async fn remove(&self, from: NaiveDate, to: NaiveDate) -> Result<u64> {
+        let from = from.num_days_from_ce();
+        let to = to.num_days_from_ce();
+        Ok(sqlx::query!(
+            "DELETE FROM closures WHERE day >= ?1 AND day <= ?2",
+            from,
+            to,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+
+    /// Ordered by day
+    pub async fn get_closures(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Closure>> {
+        let from = from.num_days_from_ce();
+        let to = to.num_days_from_ce();
+        Ok(sqlx::query!(
+            "SELECT day, reason, author FROM closures WHERE day >= ?1 AND day <= ?2 ORDER BY day",
+            from,
+            to,
+        )
+        .map(|r| Closure {
+            day: NaiveDate::from_num_days_from_ce_opt(r.day as i32).unwrap(),
+            reason: r.reason,
+            author: Uid::from(r.author),
+        })
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
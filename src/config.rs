@@ -1,6 +1,7 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
-use serde_derive::Deserialize;
+use chrono::{NaiveTime, Weekday};
+use serde_derive::{Deserialize, Serialize};
 use teloxide::types::ChatId;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -10,32 +11,117 @@ pub struct TelegramBotConfig {
     pub private_chat_id: ChatId,
     pub public_channel_id: ChatId,
     pub alert_chat_id: ChatId,
+    /// Collect each day's announcements into a single message edited in place
+    #[serde(default)]
+    pub aggregate_announcements: bool,
+    #[serde(default)]
+    pub weekly_digest: Option<WeeklyDigestConfig>,
+    /// Sent to the last resident leaving, empty to disable
+    #[serde(default = "default_lockup_checklist")]
+    pub lockup_checklist: Vec<String>,
+    /// Where the REST API is reachable from outside, used for calendar feed links
+    #[serde(default)]
+    pub public_api_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WeeklyDigestConfig {
+    /// e.g. "Mon" or "Monday"
+    pub weekday: Weekday,
+    /// Local time, e.g. "10:00"
+    pub time: NaiveTime,
+    #[serde(default = "default_true")]
+    pub to_channel: bool,
+    #[serde(default)]
+    pub to_chat: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lockup_checklist() -> Vec<String> {
+    [
+        "Выключить паяльники и 3D-принтеры",
+        "Закрыть окна",
+        "Выключить свет",
+        "Запереть дверь",
+    ]
+    .map(str::to_owned)
+    .to_vec()
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DbConfig {
     pub sqlite_path: String,
+    /// Older visits are deleted, so /stats can't look further back
+    #[serde(default = "default_visit_history_days")]
+    pub visit_history_days: u32,
+}
+
+fn default_visit_history_days() -> u32 {
+    crate::visits::DEFAULT_VISIT_HISTORY_DAYS
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct RestApiConfig {
     pub bind_address: String,
+    /// Enables `/spaceapi.json`
+    #[serde(default)]
+    pub space_api: Option<SpaceApiConfig>,
 }
 
 impl Default for RestApiConfig {
     fn default() -> Self {
         Self {
             bind_address: "127.0.0.1:3000".to_owned(),
+            space_api: None,
         }
     }
 }
 
+/// Static part of the SpaceAPI response, see https://spaceapi.io
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SpaceApiConfig {
+    pub space: String,
+    pub logo: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SpaceApiLocation>,
+    /// e.g. `telegram: "https://t.me/..."`, `email: "..."`
+    #[serde(default)]
+    pub contact: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SpaceApiLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct ExternalCalendarConfig {
+    /// ICS file, or a directory with `.ics` files
+    pub path: PathBuf,
+    /// At least 1
+    #[serde(default = "default_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_reload_interval_secs() -> u64 {
+    10 * 60
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub telegram_bot: TelegramBotConfig,
     pub db: DbConfig,
     #[serde(default)]
     pub rest_api: RestApiConfig,
+    #[serde(default)]
+    pub external_calendar: Option<ExternalCalendarConfig>,
 }
 
 impl Config {
@@ -47,6 +133,18 @@ impl Config {
         builder
             .add_source(config::Environment::with_prefix(env_prefix))
             .build()?
-            .try_deserialize()
+            .try_deserialize::<Self>()?
+            .validated()
+    }
+
+    fn validated(self) -> Result<Self, config::ConfigError> {
+        if let Some(calendar) = &self.external_calendar
+            && calendar.reload_interval_secs == 0
+        {
+            return Err(config::ConfigError::Message(
+                "external_calendar.reload_interval_secs must be at least 1".to_owned(),
+            ));
+        }
+        Ok(self)
     }
 }
@@ -0,0 +1,235 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePool;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub organizer: Uid,
+    /// `None` means unlimited
+    pub capacity: Option<u32>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewEvent {
+    pub title: String,
+    pub description: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub organizer: Uid,
+    pub capacity: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsvpStatus {
+    Going,
+    Waitlisted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unrsvp {
+    pub removed: bool,
+    /// First person from the waitlist who got the freed place
+    pub promoted: Option<Uid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Events {
+    pool: SqlitePool,
+}
+
+impl Event {
+    /// Splits attendees in sign-up order into the ones going and the waitlist
+    pub fn split_attendees<'a>(&self, attendees: &'a [Uid]) -> (&'a [Uid], &'a [Uid]) {
+        match self.capacity {
+            Some(capacity) => attendees.split_at(attendees.len().min(capacity as usize)),
+            None => (attendees, &[]),
+        }
+    }
+
+    pub fn status_of(&self, attendees: &[Uid], person: Uid) -> Option<RsvpStatus> {
+        let (going, waitlist) = self.split_attendees(attendees);
+        if going.contains(&person) {
+            Some(RsvpStatus::Going)
+        } else if waitlist.contains(&person) {
+            Some(RsvpStatus::Waitlisted)
+        } else {
+            None
+        }
+    }
+}
+
+fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
+}
+
+struct EventRow {
+    id: i64,
+    title: String,
+    description: String,
+    starts_at: i64,
+    ends_at: i64,
+    organizer: i64,
+    capacity: i64,
+    cancelled: i64,
+}
+
+impl From<EventRow> for Event {
+    fn from(r: EventRow) -> Self {
+        Event {
+            id: r.id,
+            title: r.title,
+            description: r.description,
+            starts_at: timestamp_to_datetime(r.starts_at),
+            ends_at: timestamp_to_datetime(r.ends_at),
+            organizer: Uid::from(r.organizer),
+            capacity: (r.capacity > 0).then_some(r.capacity as u32),
+            cancelled: r.cancelled != 0,
+        }
+    }
+}
+
+impl Events {
+    pub fn new(pool: SqlitePool) -> Result<Events> {
+        Ok(Events { pool })
+    }
+
+    pub async fn create(&self, event: &NewEvent) -> Result<i64> {
+        let starts_at = event.starts_at.timestamp();
+        let ends_at = event.ends_at.timestamp();
+        let organizer: i64 = event.organizer.into();
+        let capacity = event.capacity.unwrap_or(0);
+        Ok(sqlx::query!(
+            "INSERT INTO events (title, description, starts_at, ends_at, organizer, capacity)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            event.title,
+            event.description,
+            starts_at,
+            ends_at,
+            organizer,
+            capacity,
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid())
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Event>> {
+        Ok(sqlx::query_as!(
+            EventRow,
+            "SELECT id, title, description, starts_at, ends_at, organizer, capacity, cancelled
+            FROM events WHERE id = ?1",
+            id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Event::from))
+    }
+
+    /// Events that are not cancelled and end after `from` and start before `to`, ordered by start
+    pub async fn get_events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Event>> {
+        let from = from.timestamp();
+        let to = to.timestamp();
+        Ok(sqlx::query_as!(
+            EventRow,
+            "SELECT id, title, description, starts_at, ends_at, organizer, capacity, cancelled
+            FROM events WHERE cancelled = 0 AND ends_at > ?1 AND starts_at < ?2
+            ORDER BY starts_at, id",
+            from,
+            to,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Event::from)
+        .collect())
+    }
+
+    /// Returns false if the event doesn't exist or is already cancelled
+    pub async fn cancel(&self, id: i64) -> Result<bool> {
+        Ok(sqlx::query!(
+            "UPDATE events SET cancelled = 1 WHERE id = ?1 AND cancelled = 0",
+            id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Returns false if the person has already signed up
+    pub async fn add_attendee(&self, event: i64, person: Uid) -> Result<bool> {
+        let person: i64 = person.into();
+        Ok(sqlx::query!(
+            "INSERT INTO rsvps (event, person) VALUES (?1, ?2) ON CONFLICT (event, person) DO NOTHING",
+            event,
+            person,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    pub async fn remove_attendee(&self, event: i64, person: Uid) -> Result<bool> {
+        let person: i64 = person.into();
+        Ok(sqlx::query!(
+            "DELETE FROM rsvps WHERE event = ?1 AND person = ?2",
+            event,
+            person,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// In sign-up order
+    pub async fn get_attendees(&self, event: i64) -> Result<Vec<Uid>> {
+        Ok(sqlx::query_scalar!(
+            "SELECT person FROM rsvps WHERE event = ?1 ORDER BY id",
+            event
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Uid::from)
+        .collect())
+    }
+
+    /// Upcoming events starting before `until` nobody was reminded about yet
+    pub async fn get_due_reminders(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Event>> {
+        let now = now.timestamp();
+        let until = until.timestamp();
+        Ok(sqlx::query_as!(
+            EventRow,
+            "SELECT id, title, description, starts_at, ends_at, organizer, capacity, cancelled
+            FROM events WHERE cancelled = 0 AND reminded = 0 AND starts_at > ?1 AND starts_at <= ?2
+            ORDER BY starts_at, id",
+            now,
+            until,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Event::from)
+        .collect())
+    }
+
+    pub async fn mark_reminded(&self, id: i64) -> Result<()> {
+        sqlx::query!("UPDATE events SET reminded = 1 WHERE id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
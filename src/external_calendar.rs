@@ -0,0 +1,100 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::config::ExternalCalendarConfig;
+use crate::ical::{CalendarEvent, parse_calendar};
+
+/// Recurring events are expanded this far into the past and the future of each reload
+const RECURRENCE_WINDOW: TimeDelta = TimeDelta::days(366);
+
+/// Events of an ICS file, or of all `.ics` files in a directory, kept in memory
+#[derive(Debug, Clone)]
+pub struct ExternalCalendar {
+    config: ExternalCalendarConfig,
+    events: Arc<RwLock<Vec<CalendarEvent>>>,
+}
+
+impl ExternalCalendar {
+    pub fn new(config: ExternalCalendarConfig) -> Self {
+        ExternalCalendar {
+            config,
+            events: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &ExternalCalendarConfig {
+        &self.config
+    }
+
+    /// Events overlapping the range, ordered by start
+    pub fn get_events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent> {
+        self.events
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.overlaps(from, to))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns whether the events changed
+    pub async fn reload(&self) -> Result<bool> {
+        let now = Utc::now();
+        let mut events = load_events(
+            &self.config.path,
+            now - RECURRENCE_WINDOW,
+            now + RECURRENCE_WINDOW,
+        )
+        .await?;
+        events.sort_by_key(|e| e.start.to_utc());
+        let mut current = self.events.write().unwrap();
+        if *current == events {
+            return Ok(false);
+        }
+        log::info!(
+            "Loaded {} events from {}",
+            events.len(),
+            self.config.path.display()
+        );
+        *current = events;
+        Ok(true)
+    }
+}
+
+async fn load_events(
+    path: &Path,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>> {
+    if !tokio::fs::metadata(path).await?.is_dir() {
+        return parse_calendar(&tokio::fs::read_to_string(path).await?, from, to);
+    }
+
+    let mut events = Vec::new();
+    let mut entries = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if !path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("ics"))
+        {
+            continue;
+        }
+        // one broken file shouldn't hide the rest
+        let text = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(e) => {
+                log::warn!("Failed to read {}: {:?}", path.display(), e);
+                continue;
+            }
+        };
+        match parse_calendar(&text, from, to) {
+            Ok(file_events) => events.extend(file_events),
+            Err(e) => log::warn!("Failed to parse {}: {:?}", path.display(), e),
+        }
+    }
+    Ok(events)
+}
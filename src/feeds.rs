@@ -0,0 +1,49 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use sqlx::sqlite::SqlitePool;
+
+#[derive(Debug, Clone)]
+pub struct FeedTokens {
+    pool: SqlitePool,
+}
+
+impl FeedTokens {
+    pub fn new(pool: SqlitePool) -> Result<FeedTokens> {
+        Ok(FeedTokens { pool })
+    }
+
+    /// Returns the person's token, generating one if needed
+    pub async fn get_or_create(&self, person: Uid) -> Result<String> {
+        let person: i64 = person.into();
+        sqlx::query!(
+            "INSERT INTO feed_tokens (person, token) VALUES (?1, lower(hex(randomblob(16))))
+            ON CONFLICT (person) DO NOTHING",
+            person,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(
+            sqlx::query_scalar!("SELECT token FROM feed_tokens WHERE person = ?1", person)
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Invalidates the old token, e.g. when a feed link has leaked
+    pub async fn reset(&self, person: Uid) -> Result<String> {
+        let person_int: i64 = person.into();
+        sqlx::query!("DELETE FROM feed_tokens WHERE person = ?1", person_int)
+            .execute(&self.pool)
+            .await?;
+        self.get_or_create(person).await
+    }
+
+    pub async fn get_person(&self, token: &str) -> Result<Option<Uid>> {
+        Ok(
+            sqlx::query_scalar!("SELECT person FROM feed_tokens WHERE token = ?1", token)
+                .fetch_optional(&self.pool)
+                .await?
+                .map(Uid::from),
+        )
+    }
+}
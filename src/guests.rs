@@ -0,0 +1,92 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use sqlx::sqlite::SqlitePool;
+
+const ACTOR_HISTORY_DAYS: i32 = 30;
+
+#[derive(Debug, Clone)]
+pub struct Guests {
+    pool: SqlitePool,
+}
+
+impl Guests {
+    pub fn new(pool: SqlitePool) -> Result<Guests> {
+        Ok(Guests { pool })
+    }
+
+    /// Returns the guest with this name, creating it if needed
+    pub async fn get_or_create(&self, name: &str, created_by: Uid) -> Result<Uid> {
+        let created_by: i64 = created_by.into();
+        let name_key = name.to_lowercase();
+        sqlx::query!(
+            "INSERT INTO guests (name, name_key, created_by) VALUES (?1, ?2, ?3)
+            ON CONFLICT (name_key) DO NOTHING",
+            name,
+            name_key,
+            created_by,
+        )
+        .execute(&self.pool)
+        .await?;
+        let id = sqlx::query_scalar!("SELECT id FROM guests WHERE name_key = ?1", name_key)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(Uid::from(-id))
+    }
+
+    pub async fn get_name(&self, person: Uid) -> Result<Option<String>> {
+        let id = -i64::from(person);
+        Ok(
+            sqlx::query_scalar!("SELECT name FROM guests WHERE id = ?1", id)
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
+    /// `None` means the person changed the visit themselves
+    pub async fn set_actor(&self, person: Uid, day: NaiveDate, actor: Option<Uid>) -> Result<()> {
+        let person: i64 = person.into();
+        let day = day.num_days_from_ce();
+        match actor {
+            Some(actor) => {
+                let actor: i64 = actor.into();
+                sqlx::query!(
+                    "INSERT INTO visit_actors (person, day, actor) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (person, day) DO UPDATE SET actor = ?3",
+                    person,
+                    day,
+                    actor,
+                )
+                .execute(&self.pool)
+                .await?;
+                let cutoff = day - ACTOR_HISTORY_DAYS;
+                sqlx::query!("DELETE FROM visit_actors WHERE day < ?1", cutoff)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM visit_actors WHERE person = ?1 AND day = ?2",
+                    person,
+                    day,
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get_actor(&self, person: Uid, day: NaiveDate) -> Result<Option<Uid>> {
+        let person: i64 = person.into();
+        let day = day.num_days_from_ce();
+        Ok(sqlx::query_scalar!(
+            "SELECT actor FROM visit_actors WHERE person = ?1 AND day = ?2",
+            person,
+            day,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Uid::from))
+    }
+}
@@ -0,0 +1,556 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, bail};
+use chrono::{
+    DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+use crate::utils::from_local;
+
+const PRODID: &str = "-//xecut//xecut_bot//RU";
+/// RFC 5545 limit, continuation lines start with a space
+const MAX_LINE_OCTETS: usize = 75;
+/// Days or weeks a recurrence rule is followed for, long enough for daily events started decades ago
+const MAX_RECURRENCE_PERIODS: i64 = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CalendarTime {
+    /// All-day
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarEvent {
+    /// Globally unique, stable between feed refreshes
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: CalendarTime,
+    pub end: CalendarTime,
+}
+
+fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            ';' => result.push_str("\\;"),
+            ',' => result.push_str("\\,"),
+            '\n' => result.push_str("\\n"),
+            '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn fold_line(line: &str, output: &mut String) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            output.push_str("\r\n ");
+            octets = 1;
+        }
+        output.push(c);
+        octets += c.len_utf8();
+    }
+    output.push_str("\r\n");
+}
+
+fn format_time(name: &str, time: CalendarTime) -> String {
+    match time {
+        CalendarTime::Date(date) => format!("{name};VALUE=DATE:{}", date.format("%Y%m%d")),
+        CalendarTime::DateTime(time) => format!("{name}:{}", time.format("%Y%m%dT%H%M%SZ")),
+    }
+}
+
+impl CalendarTime {
+    /// All-day values start at local midnight
+    pub fn to_utc(self) -> DateTime<Utc> {
+        match self {
+            CalendarTime::Date(date) => {
+                let midnight = date.and_time(NaiveTime::MIN);
+                from_local(midnight).unwrap_or_else(|| midnight.and_utc())
+            }
+            CalendarTime::DateTime(time) => time,
+        }
+    }
+}
+
+impl CalendarEvent {
+    pub fn is_all_day(&self) -> bool {
+        matches!(self.start, CalendarTime::Date(_))
+    }
+
+    pub fn overlaps(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        self.start.to_utc() < to && self.end.to_utc() > from
+    }
+
+    pub fn all_day(uid: String, summary: String, description: String, day: NaiveDate) -> Self {
+        CalendarEvent {
+            uid,
+            summary,
+            description,
+            start: CalendarTime::Date(day),
+            // DTEND is exclusive
+            end: CalendarTime::Date(day + TimeDelta::days(1)),
+        }
+    }
+}
+
+/// Renders a complete VCALENDAR, `now` is used as the DTSTAMP of every event
+pub fn write_calendar(name: &str, events: &[CalendarEvent], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        format!("PRODID:{PRODID}"),
+        "CALSCALE:GREGORIAN".to_owned(),
+        "METHOD:PUBLISH".to_owned(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{}", escape_text(&event.uid)));
+        lines.push(format_time("DTSTAMP", CalendarTime::DateTime(now)));
+        lines.push(format_time("DTSTART", event.start));
+        lines.push(format_time("DTEND", event.end));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if !event.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(&event.description)));
+        }
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    let mut output = String::new();
+    for line in lines {
+        fold_line(&line, &mut output);
+    }
+    output
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Lines with continuations joined back
+fn unfold_lines(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        if let Some(continuation) = line.strip_prefix([' ', '\t'])
+            && let Some(last) = lines.last_mut()
+        {
+            last.push_str(continuation);
+        } else if !line.is_empty() {
+            lines.push(line.to_owned());
+        }
+    }
+    lines
+}
+
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.trim_matches('"'))
+    }
+}
+
+fn parse_property(line: &str) -> Option<Property<'_>> {
+    // parameter values may be quoted and contain ':'
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(n, v)| (n.to_ascii_uppercase(), v))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+fn parse_time(property: &Property) -> Option<CalendarTime> {
+    parse_time_value(property, property.value)
+}
+
+/// `value` can be one of the comma-separated values of `property`, as in EXDATE
+fn parse_time_value(property: &Property, value: &str) -> Option<CalendarTime> {
+    let value = value.trim();
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(CalendarTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(CalendarTime::DateTime(time.and_utc()));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    // floating times and unknown zones are taken as local
+    let utc = match property.param("TZID").and_then(|tz| tz.parse::<Tz>().ok()) {
+        Some(tz) => tz
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc)),
+        None => from_local(time),
+    }?;
+    Some(CalendarTime::DateTime(utc))
+}
+
+/// Zone the wall clock time of DTSTART is kept in when repeating the event, `None` for UTC
+fn parse_zone(property: &Property) -> Option<Tz> {
+    if property.value.trim().ends_with('Z') {
+        return None;
+    }
+    Some(
+        property
+            .param("TZID")
+            .and_then(|tz| tz.parse::<Tz>().ok())
+            .unwrap_or_else(|| crate::utils::now().timezone()),
+    )
+}
+
+fn local_date(time: CalendarTime, zone: Option<Tz>) -> NaiveDate {
+    match (time, zone) {
+        (CalendarTime::Date(date), _) => date,
+        (CalendarTime::DateTime(time), None) => time.date_naive(),
+        (CalendarTime::DateTime(time), Some(tz)) => time.with_timezone(&tz).date_naive(),
+    }
+}
+
+/// The same wall clock time `days` later, `None` if it falls into a DST gap
+fn shift_days(time: CalendarTime, zone: Option<Tz>, days: i64) -> Option<CalendarTime> {
+    let delta = TimeDelta::days(days);
+    Some(match (time, zone) {
+        (CalendarTime::Date(date), _) => CalendarTime::Date(date + delta),
+        (CalendarTime::DateTime(time), None) => CalendarTime::DateTime(time + delta),
+        (CalendarTime::DateTime(time), Some(tz)) => {
+            let local = time.with_timezone(&tz).naive_local() + delta;
+            CalendarTime::DateTime(tz.from_local_datetime(&local).earliest()?.to_utc())
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// The subset of RRULE that exported hackerspace calendars use for regular meetups
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<CalendarTime>,
+    /// Empty means the weekday of DTSTART
+    by_day: Vec<Weekday>,
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        // "1MO" and the like only make sense for monthly rules
+        _ => return None,
+    })
+}
+
+impl Recurrence {
+    /// `None` for rules that aren't supported
+    fn parse(property: &Property) -> Option<Self> {
+        let mut frequency = None;
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+        };
+        for part in property.value.trim().split(';') {
+            let (name, value) = part.split_once('=')?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => recurrence.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => recurrence.count = Some(value.parse().ok()?),
+                "UNTIL" => recurrence.until = Some(parse_time_value(property, value)?),
+                "BYDAY" => {
+                    recurrence.by_day = value
+                        .split(',')
+                        .map(|d| parse_weekday(&d.trim().to_ascii_uppercase()))
+                        .collect::<Option<_>>()?
+                }
+                "WKST" => {}
+                _ => return None,
+            }
+        }
+        recurrence.frequency = frequency?;
+        Some(recurrence)
+    }
+
+    /// Starts of the occurrences that begin before `to`, DTSTART included
+    fn starts(
+        &self,
+        start: CalendarTime,
+        zone: Option<Tz>,
+        to: DateTime<Utc>,
+    ) -> Vec<CalendarTime> {
+        let first_day = local_date(start, zone);
+        let weekday_offset = i64::from(first_day.weekday().num_days_from_monday());
+        let mut starts = Vec::new();
+        for period in 0..MAX_RECURRENCE_PERIODS {
+            let offsets = match self.frequency {
+                Frequency::Daily => {
+                    let offset = period * self.interval;
+                    let weekday = (first_day + TimeDelta::days(offset)).weekday();
+                    if self.by_day.is_empty() || self.by_day.contains(&weekday) {
+                        vec![offset]
+                    } else {
+                        vec![]
+                    }
+                }
+                Frequency::Weekly if self.by_day.is_empty() => vec![period * self.interval * 7],
+                Frequency::Weekly => {
+                    let week = period * self.interval * 7 - weekday_offset;
+                    let mut offsets = self
+                        .by_day
+                        .iter()
+                        .map(|d| week + i64::from(d.num_days_from_monday()))
+                        .filter(|offset| *offset >= 0)
+                        .collect::<Vec<_>>();
+                    offsets.sort();
+                    offsets.dedup();
+                    offsets
+                }
+            };
+            for offset in offsets {
+                let Some(time) = shift_days(start, zone, offset) else {
+                    continue;
+                };
+                if time.to_utc() >= to
+                    || self.until.is_some_and(|u| time.to_utc() > u.to_utc())
+                    || self.count.is_some_and(|c| starts.len() >= c)
+                {
+                    return starts;
+                }
+                starts.push(time);
+            }
+        }
+        starts
+    }
+}
+
+fn occurrence_uid(uid: &str, start: CalendarTime) -> String {
+    match start {
+        CalendarTime::Date(date) => format!("{uid}/{}", date.format("%Y%m%d")),
+        CalendarTime::DateTime(time) => format!("{uid}/{}", time.format("%Y%m%dT%H%M%SZ")),
+    }
+}
+
+#[derive(Default)]
+struct EventBuilder {
+    uid: String,
+    summary: String,
+    description: String,
+    start: Option<CalendarTime>,
+    end: Option<CalendarTime>,
+    cancelled: bool,
+    zone: Option<Tz>,
+    recurrence: Option<Result<Recurrence, String>>,
+    exdates: Vec<CalendarTime>,
+    /// Set for a changed or cancelled occurrence of a recurring event
+    recurrence_id: Option<CalendarTime>,
+}
+
+impl EventBuilder {
+    fn build(self) -> Option<CalendarEvent> {
+        if self.cancelled {
+            return None;
+        }
+        let start = self.start?;
+        let end = self.end.unwrap_or(match start {
+            CalendarTime::Date(date) => CalendarTime::Date(date + TimeDelta::days(1)),
+            CalendarTime::DateTime(_) => start,
+        });
+        Some(CalendarEvent {
+            uid: self.uid,
+            summary: self.summary,
+            description: self.description,
+            start,
+            end,
+        })
+    }
+
+    /// A recurring event is expanded into its occurrences overlapping `from..to`,
+    /// except for the ones `overridden` by their own VEVENT
+    fn build_all(
+        mut self,
+        overridden: &HashSet<(String, CalendarTime)>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<CalendarEvent> {
+        let zone = self.zone;
+        let recurrence = self.recurrence.take();
+        let exdates = std::mem::take(&mut self.exdates);
+        let recurrence_id = self.recurrence_id;
+        let Some(event) = self.build() else {
+            return vec![];
+        };
+        if let Some(recurrence_id) = recurrence_id {
+            return vec![CalendarEvent {
+                uid: occurrence_uid(&event.uid, recurrence_id),
+                ..event
+            }];
+        }
+        let recurrence = match recurrence {
+            None => return vec![event],
+            Some(Ok(recurrence)) => recurrence,
+            Some(Err(rule)) => {
+                log::warn!(
+                    "Unsupported RRULE {rule:?} of {:?}, only the first occurrence is shown",
+                    event.summary
+                );
+                return vec![event];
+            }
+        };
+
+        recurrence
+            .starts(event.start, zone, to)
+            .into_iter()
+            .filter(|start| {
+                !exdates.contains(start) && !overridden.contains(&(event.uid.clone(), *start))
+            })
+            .map(|start| CalendarEvent {
+                uid: occurrence_uid(&event.uid, start),
+                start,
+                end: match (event.start, event.end, start) {
+                    (
+                        CalendarTime::Date(first),
+                        CalendarTime::Date(end),
+                        CalendarTime::Date(day),
+                    ) => CalendarTime::Date(day + (end - first)),
+                    _ => CalendarTime::DateTime(
+                        start.to_utc() + (event.end.to_utc() - event.start.to_utc()),
+                    ),
+                },
+                ..event.clone()
+            })
+            .filter(|e| e.overlaps(from, to))
+            .collect()
+    }
+}
+
+/// Parses VEVENTs of a calendar, skipping cancelled and malformed ones.
+/// Daily and weekly recurring events are expanded into their occurrences overlapping `from..to`,
+/// other recurrence rules only get their first occurrence.
+pub fn parse_calendar(
+    text: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>> {
+    let lines = unfold_lines(text);
+    if !lines
+        .first()
+        .is_some_and(|l| l.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        bail!("not an iCalendar file");
+    }
+
+    let mut builders = Vec::new();
+    let mut current: Option<EventBuilder> = None;
+    // VALARMs and other components nested into VEVENT have their own properties
+    let mut nested = 0;
+    for line in &lines {
+        let Some(property) = parse_property(line) else {
+            continue;
+        };
+        match (property.name.as_str(), property.value.trim()) {
+            ("BEGIN", "VEVENT") => current = Some(EventBuilder::default()),
+            ("END", "VEVENT") => builders.extend(current.take()),
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() => nested -= 1,
+            _ => {}
+        }
+        let Some(event) = current.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "UID" => event.uid = unescape_text(property.value),
+            "SUMMARY" => event.summary = unescape_text(property.value),
+            "DESCRIPTION" => event.description = unescape_text(property.value),
+            "DTSTART" => {
+                event.start = parse_time(&property);
+                event.zone = parse_zone(&property);
+            }
+            "DTEND" => event.end = parse_time(&property),
+            "STATUS" => event.cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+            "RRULE" => {
+                event.recurrence =
+                    Some(Recurrence::parse(&property).ok_or_else(|| property.value.to_owned()))
+            }
+            "EXDATE" => event.exdates.extend(
+                property
+                    .value
+                    .split(',')
+                    .filter_map(|v| parse_time_value(&property, v)),
+            ),
+            "RECURRENCE-ID" => event.recurrence_id = parse_time(&property),
+            _ => {}
+        }
+    }
+
+    let overridden = builders
+        .iter()
+        .filter_map(|b| Some((b.uid.clone(), b.recurrence_id?)))
+        .collect::<HashSet<_>>();
+    Ok(builders
+        .into_iter()
+        .flat_map(|b| b.build_all(&overridden, from, to))
+        .collect())
+}
@@ -1,7 +1,24 @@
+pub mod audit;
 pub mod backend;
 pub mod bot;
+pub mod callback;
+pub mod closures;
 pub mod config;
+pub mod events;
+pub mod external_calendar;
+pub mod feeds;
+pub mod guests;
+pub mod ical;
+pub mod lockups;
+pub mod motd;
+pub mod notices;
+pub mod open_requests;
+pub mod open_sessions;
+pub mod outbox;
+pub mod rate_limit;
 pub mod rest_api;
+pub mod stats;
+pub mod subscriptions;
 pub mod utils;
 pub mod visits;
 
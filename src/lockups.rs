@@ -0,0 +1,129 @@
+use crate::audit::{AuditAction, AuditEntry, AuditLog};
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use sqlx::sqlite::SqlitePool;
+
+/// Checklist the last resident leaving goes through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockup {
+    pub id: i64,
+    pub person: Uid,
+    pub day: NaiveDate,
+    /// Bit per checklist item
+    pub checked: u32,
+    pub confirmed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lockups {
+    pool: SqlitePool,
+}
+
+impl Lockup {
+    pub fn is_checked(&self, item: usize) -> bool {
+        item < 32 && self.checked & (1 << item) != 0
+    }
+}
+
+impl Lockups {
+    pub fn new(pool: SqlitePool) -> Result<Lockups> {
+        Ok(Lockups { pool })
+    }
+
+    /// `details` go into the audit log
+    pub async fn create(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        now: DateTime<Utc>,
+        details: &str,
+    ) -> Result<i64> {
+        let person_int: i64 = person.into();
+        let day = day.num_days_from_ce();
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query!(
+            "INSERT INTO lockups (person, day) VALUES (?1, ?2)",
+            person_int,
+            day,
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        AuditLog::record(
+            &mut tx,
+            &AuditEntry {
+                at: now,
+                person,
+                action: AuditAction::LockupStarted,
+                details: details.to_owned(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Lockup>> {
+        Ok(sqlx::query!(
+            "SELECT id, person, day, checked, confirmed_at FROM lockups WHERE id = ?1",
+            id,
+        )
+        .map(|r| Lockup {
+            id: r.id,
+            person: Uid::from(r.person),
+            day: NaiveDate::from_num_days_from_ce_opt(r.day as i32).unwrap(),
+            checked: r.checked as u32,
+            confirmed: r.confirmed_at.is_some(),
+        })
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Returns false if there is no such checklist or it is already confirmed
+    pub async fn toggle(&self, id: i64, item: u8) -> Result<bool> {
+        if item >= 32 {
+            return Ok(false);
+        }
+        let mask: i64 = 1 << item;
+        Ok(sqlx::query!(
+            "UPDATE lockups SET checked = (checked | ?2) - (checked & ?2)
+            WHERE id = ?1 AND confirmed_at IS NULL",
+            id,
+            mask,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Returns false if there is no such checklist or it is already confirmed
+    pub async fn confirm(&self, id: i64, now: DateTime<Utc>, details: &str) -> Result<bool> {
+        let now_ts = now.timestamp();
+        let mut tx = self.pool.begin().await?;
+        let Some(person) = sqlx::query_scalar!(
+            "UPDATE lockups SET confirmed_at = ?2 WHERE id = ?1 AND confirmed_at IS NULL
+            RETURNING person",
+            id,
+            now_ts,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(false);
+        };
+        AuditLog::record(
+            &mut tx,
+            &AuditEntry {
+                at: now,
+                person: Uid::from(person),
+                action: AuditAction::LockupConfirmed,
+                details: details.to_owned(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePool;
+
+/// Status note shown on top of the live status until it expires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Motd {
+    pub text: String,
+    pub author: Uid,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Motds {
+    pool: SqlitePool,
+}
+
+impl Motds {
+    pub fn new(pool: SqlitePool) -> Result<Motds> {
+        Ok(Motds { pool })
+    }
+
+    /// Replaces the current note
+    pub async fn set(&self, motd: &Motd) -> Result<()> {
+        let author: i64 = motd.author.into();
+        let expires_at = motd.expires_at.timestamp();
+        sqlx::query!(
+            "INSERT INTO motd (id, text, author, expires_at) VALUES (0, ?1, ?2, ?3)
+            ON CONFLICT (id) DO UPDATE
+            SET text = excluded.text, author = excluded.author, expires_at = excluded.expires_at",
+            motd.text,
+            author,
+            expires_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns false if there was no note, expired or not
+    pub async fn clear(&self) -> Result<bool> {
+        Ok(sqlx::query!("DELETE FROM motd")
+            .execute(&self.pool)
+            .await?
+            .rows_affected()
+            > 0)
+    }
+
+    /// `None` if there is no note or it has expired
+    pub async fn get(&self, now: DateTime<Utc>) -> Result<Option<Motd>> {
+        let now = now.timestamp();
+        Ok(sqlx::query!(
+            "SELECT text, author, expires_at FROM motd WHERE expires_at > ?1",
+            now,
+        )
+        .map(|r| Motd {
+            text: r.text,
+            author: Uid::from(r.author),
+            expires_at: DateTime::from_timestamp(r.expires_at, 0).unwrap_or_default(),
+        })
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePool;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notice {
+    pub id: i64,
+    pub author: Uid,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notices {
+    pool: SqlitePool,
+}
+
+fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
+}
+
+impl Notices {
+    pub fn new(pool: SqlitePool) -> Result<Notices> {
+        Ok(Notices { pool })
+    }
+
+    pub async fn add(
+        &self,
+        author: Uid,
+        text: &str,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<i64> {
+        let author: i64 = author.into();
+        let created_at = created_at.timestamp();
+        let expires_at = expires_at.timestamp();
+        Ok(sqlx::query!(
+            "INSERT INTO notices (author, text, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            author,
+            text,
+            created_at,
+            expires_at,
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid())
+    }
+
+    /// Only the author can remove a notice, returns false otherwise or if it doesn't exist
+    pub async fn remove(&self, id: i64, author: Uid) -> Result<bool> {
+        let author: i64 = author.into();
+        Ok(sqlx::query!(
+            "DELETE FROM notices WHERE id = ?1 AND author = ?2",
+            id,
+            author,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Notices that haven't expired yet, oldest first
+    pub async fn get_notices(&self, now: DateTime<Utc>) -> Result<Vec<Notice>> {
+        let now = now.timestamp();
+        Ok(sqlx::query!(
+            "SELECT id, author, text, created_at, expires_at FROM notices
+            WHERE expires_at > ?1 ORDER BY created_at, id",
+            now,
+        )
+        .map(|r| Notice {
+            id: r.id,
+            author: Uid::from(r.author),
+            text: r.text,
+            created_at: timestamp_to_datetime(r.created_at),
+            expires_at: timestamp_to_datetime(r.expires_at),
+        })
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
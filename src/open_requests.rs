@@ -0,0 +1,137 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use sqlx::sqlite::SqlitePool;
+
+/// Purpose of the visit planned for the resident who agreed to host
+pub const HOST_VISIT_PURPOSE: &str = "встречаю гостей";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenRequest {
+    pub id: i64,
+    pub requester: Uid,
+    pub day: NaiveDate,
+    /// `None` means any time works
+    pub time: Option<NaiveTime>,
+    pub comment: String,
+    /// Resident who committed to open the space
+    pub host: Option<Uid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenRequests {
+    pool: SqlitePool,
+}
+
+struct OpenRequestRow {
+    id: i64,
+    requester: i64,
+    day: i64,
+    time: String,
+    comment: String,
+    host: Option<i64>,
+}
+
+impl From<OpenRequestRow> for OpenRequest {
+    fn from(r: OpenRequestRow) -> Self {
+        OpenRequest {
+            id: r.id,
+            requester: Uid::from(r.requester),
+            day: NaiveDate::from_num_days_from_ce_opt(r.day as i32).unwrap(),
+            time: NaiveTime::parse_from_str(&r.time, "%H:%M").ok(),
+            comment: r.comment,
+            host: r.host.map(Uid::from),
+        }
+    }
+}
+
+impl OpenRequests {
+    pub fn new(pool: SqlitePool) -> Result<OpenRequests> {
+        Ok(OpenRequests { pool })
+    }
+
+    /// Returns `None` if the person has already asked for that day
+    pub async fn create(
+        &self,
+        requester: Uid,
+        day: NaiveDate,
+        time: Option<NaiveTime>,
+        comment: &str,
+    ) -> Result<Option<i64>> {
+        let requester: i64 = requester.into();
+        let day = day.num_days_from_ce();
+        let time = time
+            .map(|t| t.format("%H:%M").to_string())
+            .unwrap_or_default();
+        let result = sqlx::query!(
+            "INSERT INTO open_requests (requester, day, time, comment) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (requester, day) DO NOTHING",
+            requester,
+            day,
+            time,
+            comment,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<OpenRequest>> {
+        Ok(sqlx::query_as!(
+            OpenRequestRow,
+            "SELECT id, requester, day, time, comment, host FROM open_requests WHERE id = ?1",
+            id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(OpenRequest::from))
+    }
+
+    /// Ordered by day
+    pub async fn get_requests(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<OpenRequest>> {
+        let from = from.num_days_from_ce();
+        let to = to.num_days_from_ce();
+        Ok(sqlx::query_as!(
+            OpenRequestRow,
+            "SELECT id, requester, day, time, comment, host FROM open_requests
+            WHERE day >= ?1 AND day <= ?2 ORDER BY day, time, id",
+            from,
+            to,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(OpenRequest::from)
+        .collect())
+    }
+
+    /// Returns false if somebody is already hosting
+    pub async fn set_host(&self, id: i64, host: Uid) -> Result<bool> {
+        let host: i64 = host.into();
+        Ok(sqlx::query!(
+            "UPDATE open_requests SET host = ?2 WHERE id = ?1 AND host IS NULL",
+            id,
+            host,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Deletes the request so the person can ask again, returns it if there was one
+    pub async fn remove(&self, requester: Uid, day: NaiveDate) -> Result<Option<OpenRequest>> {
+        let requester: i64 = requester.into();
+        let day = day.num_days_from_ce();
+        Ok(sqlx::query_as!(
+            OpenRequestRow,
+            "DELETE FROM open_requests WHERE requester = ?1 AND day = ?2
+            RETURNING id, requester, day, time, comment, host",
+            requester,
+            day,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(OpenRequest::from))
+    }
+}
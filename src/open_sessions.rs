@@ -0,0 +1,200 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePool;
+
+/// Time during which a resident declared the space open to guests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenSession {
+    pub id: i64,
+    pub opener: Uid,
+    pub note: String,
+    pub opened_at: DateTime<Utc>,
+    pub closes_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenSessions {
+    pool: SqlitePool,
+}
+
+impl OpenSession {
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.closed_at.is_none() && self.closes_at > now
+    }
+
+    /// When the session started or ended, whichever happened last
+    pub fn last_change(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self.closed_at {
+            Some(closed_at) => closed_at,
+            None if self.closes_at <= now => self.closes_at,
+            None => self.opened_at,
+        }
+    }
+}
+
+fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
+}
+
+struct OpenSessionRow {
+    id: i64,
+    opener: i64,
+    note: String,
+    opened_at: i64,
+    closes_at: i64,
+    closed_at: Option<i64>,
+}
+
+impl From<OpenSessionRow> for OpenSession {
+    fn from(r: OpenSessionRow) -> Self {
+        OpenSession {
+            id: r.id,
+            opener: Uid::from(r.opener),
+            note: r.note,
+            opened_at: timestamp_to_datetime(r.opened_at),
+            closes_at: timestamp_to_datetime(r.closes_at),
+            closed_at: r.closed_at.map(timestamp_to_datetime),
+        }
+    }
+}
+
+impl OpenSessions {
+    pub fn new(pool: SqlitePool) -> Result<OpenSessions> {
+        Ok(OpenSessions { pool })
+    }
+
+    /// Starts a new session, closing the running one if there is any
+    pub async fn open(
+        &self,
+        opener: Uid,
+        note: &str,
+        now: DateTime<Utc>,
+        closes_at: DateTime<Utc>,
+    ) -> Result<OpenSession> {
+        let opener_int: i64 = opener.into();
+        let now_ts = now.timestamp();
+        let closes_at_ts = closes_at.timestamp();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "UPDATE open_sessions SET closed_at = min(closes_at, ?1) WHERE closed_at IS NULL",
+            now_ts,
+        )
+        .execute(&mut *tx)
+        .await?;
+        let id = sqlx::query!(
+            "INSERT INTO open_sessions (opener, note, opened_at, closes_at) VALUES (?1, ?2, ?3, ?4)",
+            opener_int,
+            note,
+            now_ts,
+            closes_at_ts,
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        tx.commit().await?;
+        Ok(OpenSession {
+            id,
+            opener,
+            note: note.to_owned(),
+            opened_at: timestamp_to_datetime(now_ts),
+            closes_at: timestamp_to_datetime(closes_at_ts),
+            closed_at: None,
+        })
+    }
+
+    /// The most recent session, running or not
+    pub async fn get_latest(&self) -> Result<Option<OpenSession>> {
+        Ok(sqlx::query_as!(
+            OpenSessionRow,
+            "SELECT id, opener, note, opened_at, closes_at, closed_at
+            FROM open_sessions ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(OpenSession::from))
+    }
+
+    /// Returns false if the session is already closed
+    pub async fn close(&self, id: i64, now: DateTime<Utc>) -> Result<bool> {
+        let now = now.timestamp();
+        Ok(sqlx::query!(
+            "UPDATE open_sessions SET closed_at = min(closes_at, ?2)
+            WHERE id = ?1 AND closed_at IS NULL",
+            id,
+            now,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Moves the closing time, the opener gets reminded again before the new one
+    pub async fn extend(&self, id: i64, closes_at: DateTime<Utc>) -> Result<bool> {
+        let closes_at = closes_at.timestamp();
+        Ok(sqlx::query!(
+            "UPDATE open_sessions SET closes_at = ?2, reminded = 0
+            WHERE id = ?1 AND closed_at IS NULL",
+            id,
+            closes_at,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Running sessions closing before `until` whose opener wasn't reminded yet
+    pub async fn get_due_reminders(&self, until: DateTime<Utc>) -> Result<Vec<OpenSession>> {
+        let until = until.timestamp();
+        Ok(sqlx::query_as!(
+            OpenSessionRow,
+            "SELECT id, opener, note, opened_at, closes_at, closed_at
+            FROM open_sessions WHERE closed_at IS NULL AND reminded = 0 AND closes_at <= ?1",
+            until,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(OpenSession::from)
+        .collect())
+    }
+
+    pub async fn mark_reminded(&self, id: i64) -> Result<()> {
+        sqlx::query!("UPDATE open_sessions SET reminded = 1 WHERE id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Closes sessions past their closing time and returns them
+    pub async fn close_expired(&self, now: DateTime<Utc>) -> Result<Vec<OpenSession>> {
+        let now = now.timestamp();
+        let mut tx = self.pool.begin().await?;
+        let expired = sqlx::query_as!(
+            OpenSessionRow,
+            "SELECT id, opener, note, opened_at, closes_at, closed_at
+            FROM open_sessions WHERE closed_at IS NULL AND closes_at <= ?1",
+            now,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE open_sessions SET closed_at = closes_at
+            WHERE closed_at IS NULL AND closes_at <= ?1",
+            now,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(expired
+            .into_iter()
+            .map(|r| OpenSession {
+                closed_at: Some(timestamp_to_datetime(r.closes_at)),
+                ..OpenSession::from(r)
+            })
+            .collect())
+    }
+}
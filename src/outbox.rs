@@ -0,0 +1,119 @@
+use crate::backend::Uid;
+use crate::visits::{VisitStatus, VisitUpdate};
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Announcement {
+    CheckIn(VisitUpdate),
+    Plan(VisitUpdate),
+    Unplan { person: Uid, day: NaiveDate },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub announcement: Announcement,
+    pub attempts: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    pool: SqlitePool,
+}
+
+impl Announcement {
+    fn kind(&self) -> i32 {
+        match self {
+            Announcement::CheckIn(_) => 0,
+            Announcement::Plan(_) => 1,
+            Announcement::Unplan { .. } => 2,
+        }
+    }
+
+    fn from_row(kind: i64, person: Uid, day: NaiveDate, purpose: Option<String>) -> Self {
+        let visit_update = |status| VisitUpdate {
+            person,
+            day,
+            purpose,
+            status,
+        };
+        match kind {
+            0 => Announcement::CheckIn(visit_update(VisitStatus::CheckedIn)),
+            1 => Announcement::Plan(visit_update(VisitStatus::Planned)),
+            _ => Announcement::Unplan { person, day },
+        }
+    }
+}
+
+impl Outbox {
+    pub fn new(pool: SqlitePool) -> Result<Outbox> {
+        Ok(Outbox { pool })
+    }
+
+    /// Meant to be called inside the transaction that makes the announced change
+    pub async fn enqueue(conn: &mut SqliteConnection, announcement: &Announcement) -> Result<()> {
+        let kind = announcement.kind();
+        let (person, day, purpose) = match announcement {
+            Announcement::CheckIn(v) | Announcement::Plan(v) => {
+                (v.person, v.day, v.purpose.clone())
+            }
+            Announcement::Unplan { person, day } => (*person, *day, None),
+        };
+        let person: i64 = person.into();
+        let day = day.num_days_from_ce();
+        sqlx::query!(
+            "INSERT INTO outbox (kind, person, day, purpose) VALUES (?1, ?2, ?3, ?4)",
+            kind,
+            person,
+            day,
+            purpose,
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Entries are only ordered per person and day: an entry waits for the earlier ones
+    /// about the same visit, but not for anything else failing
+    pub async fn get_due(&self, now: i64) -> Result<Vec<OutboxEntry>> {
+        Ok(sqlx::query!(
+            "SELECT id, kind, person, day, purpose, attempts FROM outbox
+            WHERE next_attempt_at <= ?1 AND NOT EXISTS (
+                SELECT 1 FROM outbox earlier
+                WHERE earlier.person = outbox.person AND earlier.day = outbox.day AND earlier.id < outbox.id
+            )
+            ORDER BY id",
+            now,
+        )
+        .map(|r| {
+            let day = chrono::NaiveDate::from_num_days_from_ce_opt(r.day as i32).unwrap();
+            OutboxEntry {
+                id: r.id,
+                announcement: Announcement::from_row(r.kind, Uid::from(r.person), day, r.purpose),
+                attempts: r.attempts,
+            }
+        })
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM outbox WHERE id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn reschedule(&self, id: i64, next_attempt_at: i64) -> Result<()> {
+        sqlx::query!(
+            "UPDATE outbox SET attempts = attempts + 1, next_attempt_at = ?2 WHERE id = ?1",
+            id,
+            next_attempt_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde_derive::Serialize;
+use teloxide::{
+    RequestError,
+    requests::{Output, Request},
+    types::ChatId,
+};
+use tokio::{sync::Notify, time::Instant};
+
+// https://core.telegram.org/bots/faq#my-bot-is-hitting-limits-how-do-i-avoid-this
+const GLOBAL_LIMIT: (usize, Duration) = (30, Duration::from_secs(1));
+const PRIVATE_CHAT_LIMIT: (usize, Duration) = (1, Duration::from_secs(1));
+const GROUP_CHAT_LIMIT: (usize, Duration) = (20, Duration::from_secs(60));
+
+const MAX_RETRIES: usize = 3;
+const LOW_PRIORITY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Replies and announcements caused by user actions
+    High,
+    /// Background work like live status edits and notifications
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct QueueMetrics {
+    pub waiting_high: usize,
+    pub waiting_low: usize,
+    pub sent: u64,
+    pub retried_after: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct State {
+    global: VecDeque<Instant>,
+    chats: HashMap<ChatId, VecDeque<Instant>>,
+    paused_until: Option<Instant>,
+    /// High priority requests waiting for a slot, by the chat they post to
+    waiting_high_chats: HashMap<Option<ChatId>, usize>,
+    metrics: QueueMetrics,
+}
+
+/// Returns the earliest instant a request fits into the sliding window.
+fn next_slot(
+    sent: &mut VecDeque<Instant>,
+    (limit, window): (usize, Duration),
+    now: Instant,
+) -> Instant {
+    while sent.front().is_some_and(|t| *t + window <= now) {
+        sent.pop_front();
+    }
+    if sent.len() < limit {
+        now
+    } else {
+        sent[sent.len() - limit] + window
+    }
+}
+
+impl State {
+    fn add_waiting(&mut self, priority: Priority, chat: Option<ChatId>) {
+        match priority {
+            Priority::High => {
+                self.metrics.waiting_high += 1;
+                *self.waiting_high_chats.entry(chat).or_default() += 1;
+            }
+            Priority::Low => self.metrics.waiting_low += 1,
+        }
+    }
+
+    fn remove_waiting(&mut self, priority: Priority, chat: Option<ChatId>) {
+        match priority {
+            Priority::High => {
+                self.metrics.waiting_high -= 1;
+                if let Some(count) = self.waiting_high_chats.get_mut(&chat) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.waiting_high_chats.remove(&chat);
+                    }
+                }
+            }
+            Priority::Low => self.metrics.waiting_low -= 1,
+        }
+    }
+
+    fn chat_slot(&mut self, chat: ChatId, now: Instant) -> Instant {
+        let limit = if chat.is_user() {
+            PRIVATE_CHAT_LIMIT
+        } else {
+            GROUP_CHAT_LIMIT
+        };
+        next_slot(self.chats.entry(chat).or_default(), limit, now)
+    }
+
+    /// High priority requests only hold back low priority ones that compete for the same slot:
+    /// either the same chat, or the global window when their own chat window is open.
+    /// A busy group chat must not stall background work in every other chat.
+    fn high_priority_ahead(&mut self, chat: Option<ChatId>, now: Instant) -> bool {
+        let waiting = self.waiting_high_chats.keys().copied().collect::<Vec<_>>();
+        waiting.into_iter().any(|high_chat| {
+            high_chat == chat || high_chat.is_none_or(|c| self.chat_slot(c, now) <= now)
+        })
+    }
+
+    /// Takes a slot if one is available, otherwise returns when to try again.
+    fn try_acquire(
+        &mut self,
+        priority: Priority,
+        chat: Option<ChatId>,
+        now: Instant,
+    ) -> Option<Instant> {
+        if priority == Priority::Low && self.high_priority_ahead(chat, now) {
+            return Some(now + LOW_PRIORITY_POLL_INTERVAL);
+        }
+
+        let mut slot = next_slot(&mut self.global, GLOBAL_LIMIT, now);
+        if let Some(paused_until) = self.paused_until {
+            slot = slot.max(paused_until);
+        }
+        if let Some(chat) = chat {
+            slot = slot.max(self.chat_slot(chat, now));
+        }
+        if slot > now {
+            return Some(slot);
+        }
+
+        self.global.push_back(now);
+        if let Some(chat) = chat {
+            self.chats.entry(chat).or_default().push_back(now);
+        }
+        None
+    }
+}
+
+/// Counts a request as waiting until dropped, so cancelled requests don't stay in the queue
+struct Waiting<'a> {
+    limiter: &'a RateLimiter,
+    priority: Priority,
+    chat: Option<ChatId>,
+}
+
+impl<'a> Waiting<'a> {
+    fn new(limiter: &'a RateLimiter, priority: Priority, chat: Option<ChatId>) -> Self {
+        limiter.state.lock().unwrap().add_waiting(priority, chat);
+        Waiting {
+            limiter,
+            priority,
+            chat,
+        }
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.limiter
+            .state
+            .lock()
+            .unwrap()
+            .remove_waiting(self.priority, self.chat);
+        self.limiter.released.notify_waiters();
+    }
+}
+
+/// Central scheduler for all outgoing Telegram requests.
+///
+/// Keeps requests within Telegram's global and per-chat limits, lets user-facing requests go
+/// before background ones and retries requests rejected with `RetryAfter`.
+#[derive(Default)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn metrics(&self) -> QueueMetrics {
+        self.state.lock().unwrap().metrics
+    }
+
+    /// Waits until a request to `chat` fits into the limits and takes the slot
+    pub async fn acquire(&self, priority: Priority, chat: Option<ChatId>) {
+        let _waiting = Waiting::new(self, priority, chat);
+        loop {
+            let retry_at = self
+                .state
+                .lock()
+                .unwrap()
+                .try_acquire(priority, chat, Instant::now());
+            let Some(retry_at) = retry_at else {
+                break;
+            };
+            tokio::select! {
+                _ = tokio::time::sleep_until(retry_at) => {}
+                _ = self.released.notified() => {}
+            }
+        }
+    }
+
+    /// Sends `request` once the limits allow it. `chat` is the chat the request posts to, if any.
+    pub async fn send<R>(
+        &self,
+        priority: Priority,
+        chat: Option<ChatId>,
+        request: R,
+    ) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        let mut retries = 0;
+        loop {
+            self.acquire(priority, chat).await;
+            let result = request.send_ref().await;
+            let mut state = self.state.lock().unwrap();
+            match result {
+                Err(RequestError::RetryAfter(after)) if retries < MAX_RETRIES => {
+                    log::warn!("Telegram asked to retry after {:?}", after.duration());
+                    retries += 1;
+                    state.metrics.retried_after += 1;
+                    let paused_until = Instant::now() + after.duration();
+                    state.paused_until = state.paused_until.max(Some(paused_until));
+                }
+                Err(e) => {
+                    state.metrics.failed += 1;
+                    return Err(e);
+                }
+                Ok(output) => {
+                    state.metrics.sent += 1;
+                    return Ok(output);
+                }
+            }
+        }
+    }
+}
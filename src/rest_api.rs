@@ -1,27 +1,53 @@
-use std::sync::Weak;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
 
 use anyhow::{Error, Result};
 use axum::{
-    Router,
-    extract::State,
-    http::StatusCode,
+    Json, Router,
+    extract::{Query, State},
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
     routing::get,
 };
+use chrono::{DateTime, TimeDelta, Utc};
 use derive_where::derive_where;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
 use tower_http::catch_panic::CatchPanicLayer;
 
-use crate::{VisitStatus, backend::Backend, config::RestApiConfig, utils::today};
+use crate::{
+    Visit, VisitStatus,
+    backend::Backend,
+    config::{RestApiConfig, SpaceApiConfig},
+    events::{Event, RsvpStatus},
+    ical::{CalendarEvent, CalendarTime, write_calendar},
+    rate_limit::QueueMetrics,
+    stats::{Stats, StatsPeriod},
+    utils::{to_local, today},
+};
+
+const FEED_DAYS: i64 = 90;
+const CALENDAR_NAME: &str = "Хакспейс Xecut";
+// stats check every visitor's membership with Telegram, too much for each request
+const STATS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
 
 #[derive_where(Clone)]
 pub struct RestApi<B: Backend> {
     config: RestApiConfig,
     backend: Weak<B>,
+    stats_cache: Arc<Mutex<HashMap<StatsPeriod, (Instant, Stats)>>>,
 }
 
 impl<B: Backend> RestApi<B> {
     pub fn new(config: RestApiConfig, backend: Weak<B>) -> Self {
-        RestApi { config, backend }
+        RestApi {
+            config,
+            backend,
+            stats_cache: Default::default(),
+        }
     }
 
     pub async fn run(self) -> Result<()> {
@@ -41,6 +67,13 @@ impl<B: Backend> RestApi<B> {
     fn router(self) -> Router<()> {
         Router::new()
             .route("/checked_in_count", get(Self::checked_in_count))
+            .route("/telegram_queue", get(Self::telegram_queue))
+            .route("/stats", get(Self::stats))
+            .route("/events", get(Self::events))
+            .route("/spaceapi.json", get(Self::space_api))
+            .route("/motd", get(Self::motd))
+            .route("/calendar.ics", get(Self::calendar))
+            .route("/my_calendar.ics", get(Self::my_calendar))
             .layer(CatchPanicLayer::new())
             .with_state(self)
     }
@@ -61,6 +94,282 @@ impl<B: Backend> RestApi<B> {
 
         Ok(format!("{checked_in}"))
     }
+
+    async fn stats(
+        State(state): State<RestApi<B>>,
+        Query(query): Query<StatsQuery>,
+    ) -> Result<Json<Stats>, ApiError> {
+        if let Some((computed_at, stats)) = state.stats_cache.lock().unwrap().get(&query.period)
+            && computed_at.elapsed() < STATS_CACHE_TTL
+        {
+            return Ok(Json(stats.clone()));
+        }
+
+        let (from, to) = query.period.range(today());
+        let stats = state.backend.upgrade().unwrap().get_stats(from, to).await?;
+        state
+            .stats_cache
+            .lock()
+            .unwrap()
+            .insert(query.period, (Instant::now(), stats.clone()));
+        Ok(Json(stats))
+    }
+
+    /// Open means a resident declared an open session with /open
+    async fn space_api(State(state): State<RestApi<B>>) -> Result<Response, ApiError> {
+        let Some(space) = &state.config.space_api else {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        };
+        let now = Utc::now();
+        let backend = state.backend.upgrade().unwrap();
+        let session = backend.get_open_session().await?;
+        let open_session = session.as_ref().filter(|s| s.is_open(now));
+
+        let mut messages = Vec::new();
+        if let Some(s) = open_session {
+            let mut message = format!("Открыто до {}", to_local(s.closes_at).format("%H:%M"));
+            if !s.note.is_empty() {
+                message.push_str(&format!(": {}", s.note));
+            }
+            messages.push(message);
+        }
+        if let Some(motd) = backend.get_motd().await? {
+            messages.push(motd.text);
+        }
+
+        Ok(Json(SpaceApi {
+            api_compatibility: ["15"],
+            space,
+            state: SpaceApiState {
+                open: open_session.is_some(),
+                lastchange: session.as_ref().map(|s| s.last_change(now).timestamp()),
+                message: (!messages.is_empty()).then(|| messages.join(" · ")),
+            },
+        })
+        .into_response())
+    }
+
+    /// `null` if there is no status note
+    async fn motd(State(state): State<RestApi<B>>) -> Result<Json<Option<MotdInfo>>, ApiError> {
+        let motd = state.backend.upgrade().unwrap().get_motd().await?;
+        Ok(Json(motd.map(|m| MotdInfo {
+            text: m.text,
+            expires_at: m.expires_at,
+        })))
+    }
+
+    /// Upcoming space events together with the imported ones
+    async fn events(State(state): State<RestApi<B>>) -> Result<Json<Vec<EventInfo>>, ApiError> {
+        let backend = state.backend.upgrade().unwrap();
+        let now = Utc::now();
+        let to = now + TimeDelta::days(FEED_DAYS);
+
+        let mut events = backend
+            .get_events(now, to)
+            .await?
+            .into_iter()
+            .map(|e| EventInfo {
+                id: Some(e.id),
+                title: e.title,
+                description: e.description,
+                starts_at: e.starts_at,
+                ends_at: e.ends_at,
+                all_day: false,
+                external: false,
+            })
+            .collect_vec();
+        events.extend(
+            backend
+                .get_external_events(now, to)
+                .into_iter()
+                .map(|e| EventInfo {
+                    id: None,
+                    all_day: e.is_all_day(),
+                    starts_at: e.start.to_utc(),
+                    ends_at: e.end.to_utc(),
+                    title: e.summary,
+                    description: e.description,
+                    external: true,
+                }),
+        );
+        events.sort_by_key(|e| e.starts_at);
+
+        Ok(Json(events))
+    }
+
+    /// Planned visits are only counted per day, names stay private
+    async fn calendar(State(state): State<RestApi<B>>) -> Result<Response, ApiError> {
+        let backend = state.backend.upgrade().unwrap();
+        let today = today();
+        let now = Utc::now();
+
+        let visits = backend
+            .get_visits(today, today + TimeDelta::days(FEED_DAYS))
+            .await?;
+        let mut calendar_events = public_visit_events(&visits);
+
+        let events = backend
+            .get_events(now, now + TimeDelta::days(FEED_DAYS))
+            .await?;
+        calendar_events.extend(events.iter().map(|e| event_to_calendar(e, "")));
+        calendar_events.extend(
+            backend
+                .get_external_events(now, now + TimeDelta::days(FEED_DAYS))
+                .into_iter()
+                .map(|e| CalendarEvent {
+                    summary: format!("📆 {}", e.summary),
+                    ..e
+                }),
+        );
+
+        Ok(calendar_response(write_calendar(
+            CALENDAR_NAME,
+            &calendar_events,
+            now,
+        )))
+    }
+
+    /// Visits and events of the person owning the token
+    async fn my_calendar(
+        State(state): State<RestApi<B>>,
+        Query(query): Query<FeedQuery>,
+    ) -> Result<Response, ApiError> {
+        let backend = state.backend.upgrade().unwrap();
+        let Some(person) = backend.get_feed_person(query.token).await? else {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        };
+        let today = today();
+        let now = Utc::now();
+
+        let visits = backend
+            .get_person_visits(person, today, today + TimeDelta::days(FEED_DAYS))
+            .await?;
+        let mut calendar_events = visits
+            .iter()
+            .filter(|v| v.status != VisitStatus::CheckedOut)
+            .map(|v| {
+                CalendarEvent::all_day(
+                    format!(
+                        "visit-{}-{}@xecut_bot",
+                        i64::from(person),
+                        v.day.format("%Y%m%d")
+                    ),
+                    "🗓️ Визит в хакспейс".to_owned(),
+                    v.purpose.clone(),
+                    v.day,
+                )
+            })
+            .collect_vec();
+
+        for event in backend
+            .get_events(now, now + TimeDelta::days(FEED_DAYS))
+            .await?
+        {
+            let attendees = backend.get_event_attendees(event.id).await?;
+            let suffix = match event.status_of(&attendees, person) {
+                Some(RsvpStatus::Going) => "",
+                Some(RsvpStatus::Waitlisted) => " (лист ожидания)",
+                None => continue,
+            };
+            calendar_events.push(event_to_calendar(&event, suffix));
+        }
+
+        Ok(calendar_response(write_calendar(
+            &format!("{CALENDAR_NAME}: мои планы"),
+            &calendar_events,
+            now,
+        )))
+    }
+
+    async fn telegram_queue(State(state): State<RestApi<B>>) -> Json<QueueMetrics> {
+        Json(state.backend.upgrade().unwrap().telegram_queue_metrics())
+    }
+}
+
+#[derive(Serialize)]
+struct SpaceApi<'a> {
+    /// v14 would also require `location` and `issue_report_channels`
+    api_compatibility: [&'static str; 1],
+    #[serde(flatten)]
+    space: &'a SpaceApiConfig,
+    state: SpaceApiState,
+}
+
+#[derive(Serialize)]
+struct SpaceApiState {
+    open: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lastchange: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MotdInfo {
+    text: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct EventInfo {
+    /// Only events created with the bot have ids
+    id: Option<i64>,
+    title: String,
+    description: String,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    all_day: bool,
+    /// Comes from the imported calendar
+    external: bool,
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    token: String,
+}
+
+/// One all-day event per day with the number of people coming, without names or purposes
+pub fn public_visit_events(visits: &[Visit]) -> Vec<CalendarEvent> {
+    visits
+        .iter()
+        .filter(|v| v.status != VisitStatus::CheckedOut)
+        .map(|v| v.day)
+        .counts()
+        .into_iter()
+        .sorted()
+        .map(|(day, count)| {
+            CalendarEvent::all_day(
+                format!("visits-{}@xecut_bot", day.format("%Y%m%d")),
+                format!("🗓️ Планируют зайти: {count}"),
+                String::new(),
+                day,
+            )
+        })
+        .collect()
+}
+
+fn event_to_calendar(event: &Event, suffix: &str) -> CalendarEvent {
+    CalendarEvent {
+        uid: format!("event-{}@xecut_bot", event.id),
+        summary: format!("🎪 {}{suffix}", event.title),
+        description: event.description.clone(),
+        start: CalendarTime::DateTime(event.starts_at),
+        end: CalendarTime::DateTime(event.ends_at),
+    }
+}
+
+fn calendar_response(calendar: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar,
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    #[serde(default)]
+    period: StatsPeriod,
 }
 
 // Make our own error that wraps `anyhow::Error`.
@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::backend::Uid;
+use crate::visits::{Visit, VisitStatus};
+
+const TOP_VISITORS: usize = 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsPeriod {
+    #[default]
+    Week,
+    Month,
+    Year,
+}
+
+impl StatsPeriod {
+    /// Accepts both English and Russian names
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "week" | "неделя" => Some(StatsPeriod::Week),
+            "month" | "месяц" => Some(StatsPeriod::Month),
+            "year" | "год" => Some(StatsPeriod::Year),
+            _ => None,
+        }
+    }
+
+    /// Inclusive range of days ending with `today`
+    pub fn range(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let days = match self {
+            StatsPeriod::Week => 7,
+            StatsPeriod::Month => 30,
+            StatsPeriod::Year => 365,
+        };
+        (today - TimeDelta::days(days - 1), today)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopVisitor {
+    pub person: i64,
+    pub visits: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_visits: usize,
+    pub unique_visitors: usize,
+    pub resident_visits: usize,
+    pub guest_visits: usize,
+    /// Monday first
+    pub visits_by_weekday: [usize; 7],
+    /// Over all days of the period, including ones nobody came
+    pub average_per_evening: f64,
+    /// Telegram ids, kept out of the public REST API
+    #[serde(skip_serializing)]
+    pub top_visitors: Vec<TopVisitor>,
+}
+
+/// Only visits people actually checked in for are counted, plans are ignored
+pub fn compute_stats(
+    visits: &[Visit],
+    residents: &HashSet<Uid>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Stats {
+    let attended = visits
+        .iter()
+        .filter(|v| from <= v.day && v.day <= to && v.status != VisitStatus::Planned)
+        .collect_vec();
+
+    let mut visits_by_weekday = [0; 7];
+    let mut visits_by_person = HashMap::<Uid, usize>::new();
+    for v in &attended {
+        visits_by_weekday[v.day.weekday().num_days_from_monday() as usize] += 1;
+        *visits_by_person.entry(v.person).or_default() += 1;
+    }
+
+    let resident_visits = attended
+        .iter()
+        .filter(|v| residents.contains(&v.person))
+        .count();
+    let days = (to - from).num_days() + 1;
+
+    Stats {
+        from,
+        to,
+        total_visits: attended.len(),
+        unique_visitors: visits_by_person.len(),
+        resident_visits,
+        guest_visits: attended.len() - resident_visits,
+        visits_by_weekday,
+        average_per_evening: if days > 0 {
+            attended.len() as f64 / days as f64
+        } else {
+            0.0
+        },
+        top_visitors: visits_by_person
+            .into_iter()
+            .map(|(person, visits)| TopVisitor {
+                person: person.into(),
+                visits,
+            })
+            .sorted_by_key(|t| (std::cmp::Reverse(t.visits), t.person))
+            .take(TOP_VISITORS)
+            .collect(),
+    }
+}
@@ -0,0 +1,92 @@
+use crate::backend::Uid;
+use anyhow::Result;
+use chrono::{NaiveTime, Timelike};
+use sqlx::sqlite::SqlitePool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            // wraps around midnight, e.g. 23:00-09:00
+            time >= self.from || time < self.to
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub person: Uid,
+    pub notify_close: bool,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscriptions {
+    pool: SqlitePool,
+}
+
+fn time_to_minutes(time: NaiveTime) -> i64 {
+    (time.num_seconds_from_midnight() / 60) as i64
+}
+
+fn minutes_to_time(minutes: i64) -> NaiveTime {
+    NaiveTime::from_num_seconds_from_midnight_opt((minutes * 60) as u32, 0)
+        .unwrap_or(NaiveTime::MIN)
+}
+
+impl Subscriptions {
+    pub fn new(pool: SqlitePool) -> Result<Subscriptions> {
+        Ok(Subscriptions { pool })
+    }
+
+    pub async fn upsert_subscription(&self, subscription: &Subscription) -> Result<()> {
+        let person: i64 = subscription.person.into();
+        let quiet_from = subscription.quiet_hours.map(|q| time_to_minutes(q.from));
+        let quiet_to = subscription.quiet_hours.map(|q| time_to_minutes(q.to));
+        sqlx::query!(
+            "INSERT INTO subscriptions (person, notify_close, quiet_from, quiet_to) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (person) DO UPDATE SET notify_close = ?2, quiet_from = ?3, quiet_to = ?4",
+            person,
+            subscription.notify_close,
+            quiet_from,
+            quiet_to,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_subscription(&self, person: Uid) -> Result<bool> {
+        let person: i64 = person.into();
+        Ok(
+            sqlx::query!("DELETE FROM subscriptions WHERE person = ?1", person)
+                .execute(&self.pool)
+                .await?
+                .rows_affected()
+                > 0,
+        )
+    }
+
+    pub async fn get_subscriptions(&self) -> Result<Vec<Subscription>> {
+        Ok(
+            sqlx::query!("SELECT person, notify_close, quiet_from, quiet_to FROM subscriptions")
+                .map(|r| Subscription {
+                    person: Uid::from(r.person),
+                    notify_close: r.notify_close != 0,
+                    quiet_hours: r.quiet_from.zip(r.quiet_to).map(|(from, to)| QuietHours {
+                        from: minutes_to_time(from),
+                        to: minutes_to_time(to),
+                    }),
+                })
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+}
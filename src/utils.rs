@@ -1,4 +1,6 @@
-use chrono::{NaiveDate, TimeDelta, Utc};
+use chrono::{
+    DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc, Weekday,
+};
 
 const DAY_ROLLOVER_HOUR: i64 = 5;
 
@@ -8,6 +10,62 @@ pub fn now() -> chrono::DateTime<chrono_tz::Tz> {
     Utc::now().with_timezone(&TIMEZONE)
 }
 
+pub fn to_local(time: DateTime<Utc>) -> chrono::DateTime<chrono_tz::Tz> {
+    time.with_timezone(&TIMEZONE)
+}
+
+/// `None` if the local time doesn't exist because of a DST switch
+pub fn from_local(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    time.and_local_timezone(TIMEZONE)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
 pub fn today() -> NaiveDate {
-    (Utc::now().with_timezone(&TIMEZONE) - TimeDelta::hours(DAY_ROLLOVER_HOUR)).date_naive()
+    day_of(Utc::now())
+}
+
+/// First moment after `now` falling on `weekday` at `time`, in the timezone of `now`
+pub fn next_weekly<Tz: TimeZone>(
+    now: &DateTime<Tz>,
+    weekday: Weekday,
+    time: NaiveTime,
+) -> DateTime<Tz> {
+    let date = now.date_naive();
+    let days_ahead =
+        (weekday.num_days_from_monday() + 7 - date.weekday().num_days_from_monday()) % 7;
+    let mut day = date + TimeDelta::days(days_ahead.into());
+    loop {
+        // `earliest` is None only when DST skips over `time`, try the next week then
+        if let Some(at) = day
+            .and_time(time)
+            .and_local_timezone(now.timezone())
+            .earliest()
+            && at > *now
+        {
+            return at;
+        }
+        day += TimeDelta::days(7);
+    }
+}
+
+/// First moment after `now` at `time`, in the timezone of `now`
+pub fn next_daily<Tz: TimeZone>(now: &DateTime<Tz>, time: NaiveTime) -> DateTime<Tz> {
+    let mut day = now.date_naive();
+    loop {
+        if let Some(at) = day
+            .and_time(time)
+            .and_local_timezone(now.timezone())
+            .earliest()
+            && at > *now
+        {
+            return at;
+        }
+        day += TimeDelta::days(1);
+    }
+}
+
+/// Day a moment belongs to, taking the night rollover into account
+pub fn day_of(time: DateTime<Utc>) -> NaiveDate {
+    (time.with_timezone(&TIMEZONE) - TimeDelta::hours(DAY_ROLLOVER_HOUR)).date_naive()
 }
@@ -1,9 +1,10 @@
 use std::time::Duration;
 
 use crate::backend::Uid;
+use crate::outbox::{Announcement, Outbox};
 use anyhow::Result;
-use chrono::{Datelike, NaiveDate};
-use sqlx::sqlite::SqlitePool;
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
 use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,7 +22,7 @@ pub struct Visit {
     pub status: VisitStatus,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VisitUpdate {
     pub person: Uid,
     pub day: NaiveDate,
@@ -32,14 +33,32 @@ pub struct VisitUpdate {
 #[derive(Debug, Clone)]
 pub struct Visits {
     pool: SqlitePool,
+    history_days: u32,
 }
 
-const VISIT_HISTORY_DAYS: i32 = 30;
+/// Default for `DbConfig::visit_history_days`
+pub const DEFAULT_VISIT_HISTORY_DAYS: u32 = 30;
 const VISITS_CLEANUP_INTERVAL: Duration = Duration::from_secs(4 * 60 * 60); // 4 hours
 
 impl Visits {
     pub fn new(pool: SqlitePool) -> Result<Visits> {
-        Ok(Visits { pool })
+        Ok(Visits {
+            pool,
+            history_days: DEFAULT_VISIT_HISTORY_DAYS,
+        })
+    }
+
+    /// Visits older than `days` are deleted by the cleanup
+    pub fn with_history_days(self, days: u32) -> Self {
+        Visits {
+            history_days: days,
+            ..self
+        }
+    }
+
+    /// The oldest day still kept
+    pub fn history_start(&self, today: NaiveDate) -> NaiveDate {
+        today - TimeDelta::days(self.history_days.into())
     }
 
     pub async fn run(self) {
@@ -88,10 +107,78 @@ impl Visits {
         .await?)
     }
 
+    /// Visits of one person, ordered by day
+    pub async fn get_person_visits(
+        &self,
+        person: Uid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Visit>> {
+        let person_int: i64 = person.into();
+        let from_day = from.num_days_from_ce();
+        let to_day = to.num_days_from_ce();
+        Ok(sqlx::query!(
+            "SELECT day, purpose, status FROM visit WHERE person = ?1 AND day >= ?2 AND day <= ?3 ORDER BY day",
+            person_int,
+            from_day,
+            to_day,
+        )
+        .map(|r| Visit {
+            person,
+            day: chrono::NaiveDate::from_num_days_from_ce_opt(r.day as i32).unwrap(),
+            purpose: r.purpose,
+            status: VisitStatus::from(r.status as i32),
+        })
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_visit(&self, person: Uid, day: NaiveDate) -> Result<Option<Visit>> {
+        let person_int: i64 = person.into();
+        let day_int = day.num_days_from_ce();
+        Ok(sqlx::query!(
+            "SELECT purpose, status FROM visit WHERE person = ?1 AND day = ?2",
+            person_int,
+            day_int,
+        )
+        .map(|r| Visit {
+            person,
+            day,
+            purpose: r.purpose,
+            status: VisitStatus::from(r.status as i32),
+        })
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
     pub async fn upsert_visit(&self, visit_update: &VisitUpdate) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let changed_status = Self::upsert_visit_tx(&mut tx, visit_update).await?;
+        tx.commit().await?;
+        Ok(changed_status)
+    }
+
+    /// Same as `upsert_visit`, but also puts `announcement` into the outbox if the status changed
+    pub async fn upsert_visit_announced(
+        &self,
+        visit_update: &VisitUpdate,
+        announcement: &Announcement,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let changed_status = Self::upsert_visit_tx(&mut tx, visit_update).await?;
+        if changed_status {
+            Outbox::enqueue(&mut tx, announcement).await?;
+        }
+        tx.commit().await?;
+        Ok(changed_status)
+    }
+
+    async fn upsert_visit_tx(
+        tx: &mut SqliteConnection,
+        visit_update: &VisitUpdate,
+    ) -> Result<bool> {
         let person: i64 = visit_update.person.into();
         let day = visit_update.day.num_days_from_ce();
-        let mut tx = self.pool.begin().await?;
         let existing = sqlx::query!(
             "SELECT purpose, status FROM visit WHERE person = ?1 AND day = ?2",
             person,
@@ -131,17 +218,54 @@ impl Visits {
             .await?;
             changed_status = true;
         }
-        tx.commit().await?;
         Ok(changed_status)
     }
 
+    /// Returns false if there is no visit to update
+    pub async fn update_purpose(&self, person: Uid, day: NaiveDate, purpose: &str) -> Result<bool> {
+        let person: i64 = person.into();
+        let day = day.num_days_from_ce();
+        Ok(sqlx::query!(
+            "UPDATE visit SET purpose = ?3 WHERE person = ?1 AND day = ?2",
+            person,
+            day,
+            purpose,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Returns false if the person wasn't checked in, plans are left alone.
+    /// Only visits that were checked in end up `CheckedOut`, which is what stats count.
+    pub async fn check_out(&self, person: Uid, day: NaiveDate) -> Result<bool> {
+        let person: i64 = person.into();
+        let day = day.num_days_from_ce();
+        let checked_in: i32 = VisitStatus::CheckedIn.into();
+        let checked_out: i32 = VisitStatus::CheckedOut.into();
+        Ok(sqlx::query!(
+            "UPDATE visit SET status = ?4 WHERE person = ?1 AND day = ?2 AND status = ?3",
+            person,
+            day,
+            checked_in,
+            checked_out,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
     pub async fn check_out_everybody(&self, day: NaiveDate) -> Result<()> {
         let day = day.num_days_from_ce();
-        let status_int: i32 = VisitStatus::CheckedOut.into();
+        let checked_in: i32 = VisitStatus::CheckedIn.into();
+        let checked_out: i32 = VisitStatus::CheckedOut.into();
         sqlx::query!(
-            "UPDATE visit SET status = ?1 WHERE day = ?2",
-            status_int,
+            "UPDATE visit SET status = ?3 WHERE day = ?1 AND status = ?2",
             day,
+            checked_in,
+            checked_out,
         )
         .execute(&self.pool)
         .await?;
@@ -149,6 +273,31 @@ impl Visits {
     }
 
     pub async fn delete_visit(&self, person: Uid, day: NaiveDate) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        Self::delete_visit_tx(&mut conn, person, day).await
+    }
+
+    /// Same as `delete_visit`, but also puts `announcement` into the outbox if the visit existed
+    pub async fn delete_visit_announced(
+        &self,
+        person: Uid,
+        day: NaiveDate,
+        announcement: &Announcement,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = Self::delete_visit_tx(&mut tx, person, day).await?;
+        if deleted {
+            Outbox::enqueue(&mut tx, announcement).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn delete_visit_tx(
+        conn: &mut SqliteConnection,
+        person: Uid,
+        day: NaiveDate,
+    ) -> Result<bool> {
         let person: i64 = person.into();
         let day = day.num_days_from_ce();
         Ok(sqlx::query!(
@@ -156,7 +305,7 @@ impl Visits {
             person,
             day
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?
         .rows_affected()
             > 0)
@@ -164,7 +313,7 @@ impl Visits {
 
     pub async fn cleanup(&self, now: impl Datelike) -> Result<()> {
         let current_day = now.num_days_from_ce();
-        let cutoff = current_day - VISIT_HISTORY_DAYS;
+        let cutoff = current_day - self.history_days as i32;
 
         sqlx::query!("DELETE FROM visit WHERE day < ?1", cutoff)
             .execute(&self.pool)
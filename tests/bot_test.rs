@@ -0,0 +1,29 @@
+use chrono::NaiveDate;
+use xecut_bot::bot::{parse_day_purpose, strip_leading_mention};
+
+#[test]
+fn test_parse_day_purpose_relative_to_sent_day() {
+    let sent_on = NaiveDate::from_ymd_opt(2025, 10, 14).unwrap();
+    assert_eq!(
+        parse_day_purpose("завтра паять", sent_on),
+        (NaiveDate::from_ymd_opt(2025, 10, 15).unwrap(), "паять")
+    );
+    assert_eq!(
+        parse_day_purpose("послезавтра", sent_on),
+        (NaiveDate::from_ymd_opt(2025, 10, 16).unwrap(), "")
+    );
+    assert_eq!(parse_day_purpose(" паять ", sent_on), (sent_on, "паять"));
+    assert_eq!(
+        parse_day_purpose("2025-10-20 паять", sent_on),
+        (NaiveDate::from_ymd_opt(2025, 10, 20).unwrap(), "паять")
+    );
+}
+
+#[test]
+fn test_only_leading_mention_is_a_target() {
+    assert_eq!(strip_leading_mention("@bob паяю", "@bob"), Some("паяю"));
+    assert_eq!(strip_leading_mention("@bob: паяю", "@bob"), Some("паяю"));
+    assert_eq!(strip_leading_mention("@bob", "@bob"), Some(""));
+    assert_eq!(strip_leading_mention("паяю с @bob", "@bob"), None);
+    assert_eq!(strip_leading_mention("@bobby паяю", "@bob"), None);
+}
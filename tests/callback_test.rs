@@ -0,0 +1,138 @@
+use chrono::NaiveDate;
+use xecut_bot::backend::Uid;
+use xecut_bot::callback::{CallbackData, VisitsFilter, VisitsQuery, VisitsView};
+
+fn round_trip(data: CallbackData) {
+    let encoded = data.encode();
+    assert!(encoded.len() <= 64, "too long: {encoded:?}");
+    assert_eq!(CallbackData::decode(&encoded), Some(data));
+}
+
+#[test]
+fn test_round_trip() {
+    let day = NaiveDate::from_ymd_opt(2025, 10, 10).unwrap();
+    round_trip(CallbackData::CheckIn);
+    round_trip(CallbackData::CheckOut);
+    round_trip(CallbackData::PlanVisit(None));
+    round_trip(CallbackData::PlanVisit(Some(day)));
+    round_trip(CallbackData::UnplanVisit(None));
+    round_trip(CallbackData::UnplanVisit(Some(day)));
+    round_trip(CallbackData::Undo(u64::MAX));
+    round_trip(CallbackData::Rsvp {
+        event: 12,
+        going: true,
+    });
+    round_trip(CallbackData::Rsvp {
+        event: 12,
+        going: false,
+    });
+    round_trip(CallbackData::HostOpenRequest(7));
+    round_trip(CallbackData::DeleteNotice(11));
+    round_trip(CallbackData::Lockup {
+        lockup: 5,
+        item: Some(2),
+    });
+    round_trip(CallbackData::Lockup {
+        lockup: 5,
+        item: None,
+    });
+    round_trip(CallbackData::OpenSession {
+        session: 3,
+        extend: true,
+    });
+    round_trip(CallbackData::OpenSession {
+        session: 3,
+        extend: false,
+    });
+    round_trip(CallbackData::MeUnplan {
+        person: Uid::from(-42),
+        day,
+    });
+    for filter in [
+        VisitsFilter::All,
+        VisitsFilter::Residents,
+        VisitsFilter::Person(Uid::from(1234567890)),
+        VisitsFilter::Purpose("пайка: плата".to_string()),
+    ] {
+        round_trip(CallbackData::GetVisits(VisitsQuery {
+            view: VisitsView::Month,
+            page: 3,
+            filter,
+        }));
+    }
+}
+
+#[test]
+fn test_long_keyword_is_limited_for_all_pages() {
+    let keyword = "очень ".repeat(20);
+    let filter = VisitsFilter::purpose(&keyword);
+    let VisitsFilter::Purpose(ref limited) = filter else {
+        panic!("not a purpose filter: {filter:?}");
+    };
+    assert!(keyword.starts_with(limited.as_str()));
+    assert!(!limited.is_empty());
+    for page in [0, 1, u32::MAX] {
+        round_trip(CallbackData::GetVisits(VisitsQuery {
+            view: VisitsView::Month,
+            page,
+            filter: filter.clone(),
+        }));
+    }
+    assert_eq!(
+        VisitsFilter::purpose("пайка"),
+        VisitsFilter::Purpose("пайка".to_owned())
+    );
+}
+
+#[test]
+fn test_decode_legacy() {
+    assert_eq!(
+        CallbackData::decode("/checkin"),
+        Some(CallbackData::CheckIn)
+    );
+    assert_eq!(
+        CallbackData::decode("/checkout"),
+        Some(CallbackData::CheckOut)
+    );
+    assert_eq!(
+        CallbackData::decode("/planvisit"),
+        Some(CallbackData::PlanVisit(None))
+    );
+    assert_eq!(
+        CallbackData::decode("/planvisit 2025-10-10"),
+        Some(CallbackData::PlanVisit(NaiveDate::from_ymd_opt(
+            2025, 10, 10
+        )))
+    );
+    assert_eq!(
+        CallbackData::decode("/unplanvisit 2025-10-10"),
+        Some(CallbackData::UnplanVisit(NaiveDate::from_ymd_opt(
+            2025, 10, 10
+        )))
+    );
+    assert_eq!(
+        CallbackData::decode("/getvisits w 2 r"),
+        Some(CallbackData::GetVisits(VisitsQuery {
+            view: VisitsView::Week,
+            page: 2,
+            filter: VisitsFilter::Residents,
+        }))
+    );
+}
+
+#[test]
+fn test_decode_unknown() {
+    assert_eq!(CallbackData::decode(""), None);
+    assert_eq!(CallbackData::decode("/dance"), None);
+    assert_eq!(CallbackData::decode("/planvisit someday"), None);
+    assert_eq!(CallbackData::decode("1:xx"), None);
+    assert_eq!(CallbackData::decode("1:pv:notaday"), None);
+    assert_eq!(CallbackData::decode("99:ci"), None);
+    assert_eq!(CallbackData::decode("1:un:"), None);
+    assert_eq!(CallbackData::decode("1:mu:42:"), None);
+    assert_eq!(CallbackData::decode("1:ev:12:2"), None);
+    assert_eq!(CallbackData::decode("1:os:3"), None);
+    assert_eq!(CallbackData::decode("1:ho:"), None);
+    assert_eq!(CallbackData::decode("1:nd:x"), None);
+    assert_eq!(CallbackData::decode("1:lc:5:x"), None);
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDate;
+use xecut_bot::backend::Uid;
+use xecut_bot::closures::{Closure, Closures};
+
+mod common;
+
+async fn make_closures() -> Closures {
+    Closures::new(common::test_pool().await).unwrap()
+}
+
+fn day(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 12, d).unwrap()
+}
+
+#[tokio::test]
+async fn test_add_and_get() {
+    let closures = make_closures().await;
+    let author = Uid::from(1);
+
+    assert_eq!(
+        closures
+            .add(day(30), day(31), "праздники", author)
+            .await
+            .unwrap(),
+        vec![day(30), day(31)]
+    );
+    // overlapping range only reports new days, but updates the reason of all of them
+    assert_eq!(
+        closures
+            .add(day(29), day(30), "ремонт", author)
+            .await
+            .unwrap(),
+        vec![day(29)]
+    );
+
+    let closure = |d, reason: &str| Closure {
+        day: day(d),
+        reason: reason.to_string(),
+        author,
+    };
+    assert_eq!(
+        closures.get_closures(day(1), day(31)).await.unwrap(),
+        vec![
+            closure(29, "ремонт"),
+            closure(30, "ремонт"),
+            closure(31, "праздники")
+        ]
+    );
+    assert_eq!(
+        closures.get_closures(day(31), day(31)).await.unwrap(),
+        vec![closure(31, "праздники")]
+    );
+    assert!(
+        closures
+            .get_closures(day(1), day(28))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_remove() {
+    let closures = make_closures().await;
+    closures
+        .add(day(1), day(5), "", Uid::from(1))
+        .await
+        .unwrap();
+
+    assert_eq!(closures.remove(day(4), day(10)).await.unwrap(), 2);
+    assert_eq!(closures.remove(day(4), day(10)).await.unwrap(), 0);
+    assert_eq!(
+        closures.get_closures(day(1), day(31)).await.unwrap().len(),
+        3
+    );
+}
@@ -0,0 +1,13 @@
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+
+/// In-memory database with the real migrations applied
+pub async fn test_pool() -> SqlitePool {
+    // every connection to :memory: opens a separate empty database
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(":memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
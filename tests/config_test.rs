@@ -0,0 +1,41 @@
+use xecut_bot::Config;
+
+fn load(name: &str, yaml: &str) -> Result<Config, config::ConfigError> {
+    let path = std::env::temp_dir().join(format!("xecut_bot_{name}_{}.yaml", std::process::id()));
+    std::fs::write(&path, yaml).unwrap();
+    let config = Config::new("xecut_bot_test", vec![path.clone()]);
+    std::fs::remove_file(path).unwrap();
+    config
+}
+
+const BASE: &str = "
+telegram_bot:
+  bot_token: \"\"
+  public_chat_id: 0
+  private_chat_id: 0
+  public_channel_id: 0
+  alert_chat_id: 0
+db:
+  sqlite_path: \":memory:\"
+";
+
+#[test]
+fn test_zero_reload_interval_is_rejected() {
+    let config = load(
+        "reload_ok",
+        &format!("{BASE}external_calendar:\n  path: \"events.ics\"\n"),
+    )
+    .unwrap();
+    assert_eq!(config.external_calendar.unwrap().reload_interval_secs, 600);
+    assert_eq!(config.db.visit_history_days, 30);
+
+    assert!(
+        load(
+            "reload_zero",
+            &format!(
+                "{BASE}external_calendar:\n  path: \"events.ics\"\n  reload_interval_secs: 0\n"
+            )
+        )
+        .is_err()
+    );
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use xecut_bot::backend::Uid;
+use xecut_bot::events::{Events, NewEvent, RsvpStatus};
+
+mod common;
+
+async fn make_events() -> Events {
+    Events::new(common::test_pool().await).unwrap()
+}
+
+fn start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_000_000, 0).unwrap()
+}
+
+fn new_event(title: &str, starts_at: DateTime<Utc>, capacity: Option<u32>) -> NewEvent {
+    NewEvent {
+        title: title.to_string(),
+        description: "desc".to_string(),
+        starts_at,
+        ends_at: starts_at + TimeDelta::hours(3),
+        organizer: Uid::from(1),
+        capacity,
+    }
+}
+
+#[tokio::test]
+async fn test_create_get_cancel() {
+    let events = make_events().await;
+    let id = events
+        .create(&new_event("Пайка", start(), Some(10)))
+        .await
+        .unwrap();
+
+    let event = events.get(id).await.unwrap().unwrap();
+    assert_eq!(event.title, "Пайка");
+    assert_eq!(event.starts_at, start());
+    assert_eq!(event.capacity, Some(10));
+    assert!(!event.cancelled);
+
+    let range = (start() - TimeDelta::days(1), start() + TimeDelta::days(1));
+    assert_eq!(
+        events.get_events(range.0, range.1).await.unwrap(),
+        vec![event]
+    );
+
+    assert!(events.cancel(id).await.unwrap());
+    assert!(!events.cancel(id).await.unwrap());
+    assert!(events.get(id).await.unwrap().unwrap().cancelled);
+    assert_eq!(events.get_events(range.0, range.1).await.unwrap(), vec![]);
+    assert_eq!(events.get(id + 1).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_get_events_range() {
+    let events = make_events().await;
+    let later = events
+        .create(&new_event("later", start() + TimeDelta::days(3), None))
+        .await
+        .unwrap();
+    let first = events
+        .create(&new_event("first", start(), None))
+        .await
+        .unwrap();
+
+    let ids = |v: Vec<xecut_bot::events::Event>| v.into_iter().map(|e| e.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(events
+            .get_events(start(), start() + TimeDelta::days(7))
+            .await
+            .unwrap()),
+        vec![first, later]
+    );
+    // still going events are included
+    assert_eq!(
+        ids(events
+            .get_events(start() + TimeDelta::hours(1), start() + TimeDelta::days(1))
+            .await
+            .unwrap()),
+        vec![first]
+    );
+}
+
+#[tokio::test]
+async fn test_attendees_and_waitlist() {
+    let events = make_events().await;
+    let id = events
+        .create(&new_event("Пайка", start(), Some(2)))
+        .await
+        .unwrap();
+    let event = events.get(id).await.unwrap().unwrap();
+
+    for person in [3, 2, 4] {
+        assert!(events.add_attendee(id, Uid::from(person)).await.unwrap());
+    }
+    assert!(!events.add_attendee(id, Uid::from(2)).await.unwrap());
+
+    let attendees = events.get_attendees(id).await.unwrap();
+    assert_eq!(attendees, vec![Uid::from(3), Uid::from(2), Uid::from(4)]);
+    let (going, waitlist) = event.split_attendees(&attendees);
+    assert_eq!(going, &[Uid::from(3), Uid::from(2)]);
+    assert_eq!(waitlist, &[Uid::from(4)]);
+    assert_eq!(
+        event.status_of(&attendees, Uid::from(4)),
+        Some(RsvpStatus::Waitlisted)
+    );
+    assert_eq!(event.status_of(&attendees, Uid::from(5)), None);
+
+    assert!(events.remove_attendee(id, Uid::from(3)).await.unwrap());
+    assert!(!events.remove_attendee(id, Uid::from(3)).await.unwrap());
+    let attendees = events.get_attendees(id).await.unwrap();
+    assert_eq!(
+        event.status_of(&attendees, Uid::from(4)),
+        Some(RsvpStatus::Going)
+    );
+}
+
+#[tokio::test]
+async fn test_due_reminders() {
+    let events = make_events().await;
+    let soon = events
+        .create(&new_event("soon", start() + TimeDelta::hours(1), None))
+        .await
+        .unwrap();
+    events
+        .create(&new_event("later", start() + TimeDelta::days(1), None))
+        .await
+        .unwrap();
+    events
+        .create(&new_event("past", start() - TimeDelta::hours(1), None))
+        .await
+        .unwrap();
+
+    let until = start() + TimeDelta::hours(2);
+    let due = events.get_due_reminders(start(), until).await.unwrap();
+    assert_eq!(due.iter().map(|e| e.id).collect::<Vec<_>>(), vec![soon]);
+
+    events.mark_reminded(soon).await.unwrap();
+    assert_eq!(
+        events.get_due_reminders(start(), until).await.unwrap(),
+        vec![]
+    );
+}
@@ -0,0 +1,64 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use xecut_bot::backend::Uid;
+use xecut_bot::feeds::FeedTokens;
+use xecut_bot::ical::write_calendar;
+use xecut_bot::rest_api::public_visit_events;
+use xecut_bot::{Visit, VisitStatus};
+
+mod common;
+
+#[tokio::test]
+async fn test_tokens_resolve_only_their_person() {
+    let tokens = FeedTokens::new(common::test_pool().await).unwrap();
+    let token = tokens.get_or_create(Uid::from(1)).await.unwrap();
+    assert_eq!(tokens.get_or_create(Uid::from(1)).await.unwrap(), token);
+    assert_ne!(tokens.get_or_create(Uid::from(2)).await.unwrap(), token);
+
+    assert_eq!(tokens.get_person(&token).await.unwrap(), Some(Uid::from(1)));
+    assert_eq!(tokens.get_person("").await.unwrap(), None);
+    assert_eq!(tokens.get_person("not-a-token").await.unwrap(), None);
+
+    let new_token = tokens.reset(Uid::from(1)).await.unwrap();
+    assert_ne!(new_token, token);
+    assert_eq!(tokens.get_person(&token).await.unwrap(), None);
+    assert_eq!(
+        tokens.get_person(&new_token).await.unwrap(),
+        Some(Uid::from(1))
+    );
+}
+
+#[test]
+fn test_public_feed_only_counts_visits() {
+    let day = NaiveDate::from_ymd_opt(2025, 10, 20).unwrap();
+    let visit = |person: i64, day: NaiveDate, status| Visit {
+        person: Uid::from(person),
+        day,
+        purpose: "секретный проект".to_owned(),
+        status,
+    };
+    let visits = [
+        visit(123456789, day, VisitStatus::Planned),
+        visit(987654321, day, VisitStatus::CheckedIn),
+        visit(555555555, day, VisitStatus::CheckedOut),
+        visit(123456789, day.succ_opt().unwrap(), VisitStatus::Planned),
+    ];
+
+    let events = public_visit_events(&visits);
+    assert_eq!(
+        events
+            .iter()
+            .map(|e| e.summary.as_str())
+            .collect::<Vec<_>>(),
+        ["🗓️ Планируют зайти: 2", "🗓️ Планируют зайти: 1"]
+    );
+
+    let calendar = write_calendar(
+        "test",
+        &events,
+        Utc.with_ymd_and_hms(2025, 10, 18, 12, 0, 0).unwrap(),
+    );
+    assert!(!calendar.contains("секретный"));
+    for id in ["123456789", "987654321", "555555555"] {
+        assert!(!calendar.contains(id));
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::NaiveDate;
+use xecut_bot::backend::Uid;
+use xecut_bot::guests::Guests;
+
+mod common;
+
+async fn make_guests() -> Guests {
+    Guests::new(common::test_pool().await).unwrap()
+}
+
+#[tokio::test]
+async fn test_get_or_create() {
+    let guests = make_guests().await;
+    let resident = Uid::from(1);
+
+    let vasya = guests.get_or_create("Вася", resident).await.unwrap();
+    assert!(vasya.is_guest());
+    assert!(!resident.is_guest());
+    assert_eq!(guests.get_or_create("Вася", resident).await.unwrap(), vasya);
+    assert_eq!(guests.get_or_create("вася", resident).await.unwrap(), vasya);
+
+    let petya = guests.get_or_create("Petya", resident).await.unwrap();
+    assert_ne!(petya, vasya);
+    assert_eq!(
+        guests.get_name(vasya).await.unwrap(),
+        Some("Вася".to_string())
+    );
+    assert_eq!(guests.get_name(Uid::from(-100)).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_actors() {
+    let guests = make_guests().await;
+    let person = Uid::from(2);
+    let resident = Uid::from(1);
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+
+    assert_eq!(guests.get_actor(person, day).await.unwrap(), None);
+    guests.set_actor(person, day, Some(resident)).await.unwrap();
+    assert_eq!(guests.get_actor(person, day).await.unwrap(), Some(resident));
+    let other_day = NaiveDate::from_ymd_opt(2025, 8, 9).unwrap();
+    assert_eq!(guests.get_actor(person, other_day).await.unwrap(), None);
+
+    guests.set_actor(person, day, None).await.unwrap();
+    assert_eq!(guests.get_actor(person, day).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_old_actors_are_cleaned_up() {
+    let guests = make_guests().await;
+    let resident = Uid::from(1);
+    let old_day = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+
+    guests
+        .set_actor(Uid::from(2), old_day, Some(resident))
+        .await
+        .unwrap();
+    guests
+        .set_actor(Uid::from(3), day, Some(resident))
+        .await
+        .unwrap();
+    assert_eq!(guests.get_actor(Uid::from(2), old_day).await.unwrap(), None);
+    assert_eq!(
+        guests.get_actor(Uid::from(3), day).await.unwrap(),
+        Some(resident)
+    );
+}
@@ -0,0 +1,247 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use xecut_bot::ical::{CalendarEvent, CalendarTime, parse_calendar, write_calendar};
+
+fn parse_all(text: &str) -> anyhow::Result<Vec<CalendarEvent>> {
+    parse_calendar(text, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)
+}
+
+#[test]
+fn test_calendar_structure() {
+    let now = Utc.with_ymd_and_hms(2025, 10, 18, 12, 0, 0).unwrap();
+    let events = [
+        CalendarEvent::all_day(
+            "visits-20251020@xecut_bot".to_owned(),
+            "Планируют зайти: 3".to_owned(),
+            String::new(),
+            NaiveDate::from_ymd_opt(2025, 10, 20).unwrap(),
+        ),
+        CalendarEvent {
+            uid: "event-1@xecut_bot".to_owned(),
+            summary: "Пайка; плата, и\\всё".to_owned(),
+            description: "строка 1\nстрока 2".to_owned(),
+            start: CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 10, 21, 17, 0, 0).unwrap()),
+            end: CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 10, 21, 19, 30, 0).unwrap()),
+        },
+    ];
+    let calendar = write_calendar("Xecut", &events, now);
+    let lines: Vec<&str> = calendar.split("\r\n").collect();
+
+    assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+    assert_eq!(lines[lines.len() - 2], "END:VCALENDAR");
+    assert_eq!(lines.last(), Some(&""));
+    assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 2);
+    assert!(lines.contains(&"DTSTAMP:20251018T120000Z"));
+    assert!(lines.contains(&"DTSTART;VALUE=DATE:20251020"));
+    assert!(lines.contains(&"DTEND;VALUE=DATE:20251021"));
+    assert!(lines.contains(&"DTSTART:20251021T170000Z"));
+    assert!(lines.contains(&"DTEND:20251021T193000Z"));
+    assert!(lines.contains(&r"SUMMARY:Пайка\; плата\, и\\всё"));
+    assert!(lines.contains(&"DESCRIPTION:строка 1\\nстрока 2"));
+    // empty descriptions are omitted
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|l| l.starts_with("DESCRIPTION"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_long_lines_are_folded() {
+    let now = Utc.with_ymd_and_hms(2025, 10, 18, 12, 0, 0).unwrap();
+    let summary = "ж".repeat(100);
+    let calendar = write_calendar(
+        "Xecut",
+        &[CalendarEvent::all_day(
+            "uid".to_owned(),
+            summary.clone(),
+            String::new(),
+            NaiveDate::from_ymd_opt(2025, 10, 20).unwrap(),
+        )],
+        now,
+    );
+
+    for line in calendar.split("\r\n") {
+        assert!(line.len() <= 75, "too long: {line:?}");
+    }
+    let unfolded = calendar.replace("\r\n ", "");
+    assert!(unfolded.contains(&format!("SUMMARY:{summary}\r\n")));
+}
+
+#[test]
+fn test_parse_calendar() {
+    let text = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:a@example.org\r
+SUMMARY:Лекция\\, очень длинное назва\r
+ ние\r
+DESCRIPTION:строка 1\\nстрока 2\r
+DTSTART;TZID=Europe/Belgrade:20251021T190000\r
+DTEND;TZID=\"Europe/Belgrade\":20251021T210000\r
+BEGIN:VALARM\r
+DESCRIPTION:напоминание\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:b@example.org\r
+SUMMARY:Субботник\r
+DTSTART;VALUE=DATE:20251025\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:c@example.org\r
+SUMMARY:Отменено\r
+STATUS:CANCELLED\r
+DTSTART:20251022T100000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:d@example.org\r
+SUMMARY:Без начала\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+    let events = parse_all(text).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            CalendarEvent {
+                uid: "a@example.org".to_owned(),
+                summary: "Лекция, очень длинное название".to_owned(),
+                description: "строка 1\nстрока 2".to_owned(),
+                start: CalendarTime::DateTime(
+                    Utc.with_ymd_and_hms(2025, 10, 21, 17, 0, 0).unwrap()
+                ),
+                end: CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 10, 21, 19, 0, 0).unwrap()),
+            },
+            CalendarEvent::all_day(
+                "b@example.org".to_owned(),
+                "Субботник".to_owned(),
+                String::new(),
+                NaiveDate::from_ymd_opt(2025, 10, 25).unwrap(),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let now = Utc.with_ymd_and_hms(2025, 10, 18, 12, 0, 0).unwrap();
+    let events = vec![CalendarEvent {
+        uid: "event-1@xecut_bot".to_owned(),
+        summary: format!("{}; всё, \\ ", "ж".repeat(50)),
+        description: "строка 1\nстрока 2".to_owned(),
+        start: CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 10, 21, 17, 0, 0).unwrap()),
+        end: CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 10, 21, 19, 30, 0).unwrap()),
+    }];
+    assert_eq!(
+        parse_all(&write_calendar("Xecut", &events, now)).unwrap(),
+        events
+    );
+}
+
+#[test]
+fn test_parse_not_a_calendar() {
+    assert!(parse_all("").is_err());
+    assert!(parse_all("<html></html>").is_err());
+}
+
+fn starts(events: &[CalendarEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|e| match e.start {
+            CalendarTime::Date(date) => date.format("%Y-%m-%d").to_string(),
+            CalendarTime::DateTime(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        })
+        .collect()
+}
+
+fn calendar(events: &str) -> String {
+    format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{events}END:VCALENDAR\r\n")
+}
+
+#[test]
+fn test_weekly_rule_expanded_in_window() {
+    // started long before the window, crosses the end of DST on 26 October
+    let text = calendar(
+        "BEGIN:VEVENT\r
+UID:meetup@example.org\r
+SUMMARY:Митап\r
+DTSTART;TZID=Europe/Belgrade:20240103T190000\r
+DTEND;TZID=Europe/Belgrade:20240103T210000\r
+RRULE:FREQ=WEEKLY;BYDAY=WE\r
+EXDATE;TZID=Europe/Belgrade:20251022T190000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:meetup@example.org\r
+RECURRENCE-ID;TZID=Europe/Belgrade:20251029T190000\r
+SUMMARY:Митап, перенесён\r
+DTSTART;TZID=Europe/Belgrade:20251030T190000\r
+DTEND;TZID=Europe/Belgrade:20251030T210000\r
+END:VEVENT\r
+",
+    );
+    let mut events = parse_calendar(
+        &text,
+        Utc.with_ymd_and_hms(2025, 10, 13, 0, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(2025, 11, 10, 0, 0, 0).unwrap(),
+    )
+    .unwrap();
+    events.sort_by_key(|e| e.start.to_utc());
+    assert_eq!(
+        starts(&events),
+        ["2025-10-15 17:00", "2025-10-30 18:00", "2025-11-05 18:00"]
+    );
+    assert_eq!(events[1].summary, "Митап, перенесён");
+    assert_eq!(
+        events[2].end,
+        CalendarTime::DateTime(Utc.with_ymd_and_hms(2025, 11, 5, 20, 0, 0).unwrap())
+    );
+    // occurrences are separate events in our feeds
+    assert_ne!(events[0].uid, events[2].uid);
+}
+
+#[test]
+fn test_count_until_and_interval() {
+    let text = calendar(
+        "BEGIN:VEVENT\r
+UID:a@example.org\r
+SUMMARY:Курс\r
+DTSTART:20251006T170000Z\r
+RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=3\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:b@example.org\r
+SUMMARY:Хакатон\r
+DTSTART;VALUE=DATE:20251010\r
+DTEND;VALUE=DATE:20251011\r
+RRULE:FREQ=DAILY;UNTIL=20251012\r
+END:VEVENT\r
+",
+    );
+    assert_eq!(
+        starts(&parse_all(&text).unwrap()),
+        [
+            "2025-10-06 17:00",
+            "2025-10-09 17:00",
+            "2025-10-20 17:00",
+            "2025-10-10",
+            "2025-10-11",
+            "2025-10-12"
+        ]
+    );
+}
+
+#[test]
+fn test_unsupported_rule_keeps_first_occurrence() {
+    let text = calendar(
+        "BEGIN:VEVENT\r
+UID:a@example.org\r
+SUMMARY:Ежемесячно\r
+DTSTART:20251006T170000Z\r
+RRULE:FREQ=MONTHLY;BYDAY=1MO\r
+END:VEVENT\r
+",
+    );
+    assert_eq!(starts(&parse_all(&text).unwrap()), ["2025-10-06 17:00"]);
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use xecut_bot::audit::{AuditAction, AuditEntry, AuditLog};
+use xecut_bot::backend::Uid;
+use xecut_bot::lockups::Lockups;
+
+mod common;
+
+async fn make_lockups() -> (Lockups, AuditLog) {
+    let pool = common::test_pool().await;
+    let lockups = Lockups::new(pool.clone()).unwrap();
+    let audit_log = AuditLog::new(pool.clone()).unwrap();
+    (lockups, audit_log)
+}
+
+fn start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_000_000, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_checklist_flow_is_audited() {
+    let (lockups, audit_log) = make_lockups().await;
+    let person = Uid::from(1);
+    let day = NaiveDate::from_ymd_opt(2025, 10, 20).unwrap();
+
+    let id = lockups
+        .create(person, day, start(), "гости в спейсе: Вася")
+        .await
+        .unwrap();
+
+    assert!(lockups.toggle(id, 0).await.unwrap());
+    assert!(lockups.toggle(id, 2).await.unwrap());
+    assert!(lockups.toggle(id, 2).await.unwrap());
+    assert!(!lockups.toggle(id, 40).await.unwrap());
+    let lockup = lockups.get(id).await.unwrap().unwrap();
+    assert_eq!(lockup.person, person);
+    assert_eq!(lockup.day, day);
+    assert!(lockup.is_checked(0));
+    assert!(!lockup.is_checked(2));
+    assert!(!lockup.confirmed);
+
+    let confirmed_at = start() + TimeDelta::minutes(5);
+    assert!(
+        lockups
+            .confirm(id, confirmed_at, "отмечено 1/2")
+            .await
+            .unwrap()
+    );
+    assert!(!lockups.confirm(id, confirmed_at, "").await.unwrap());
+    // confirmed lists are frozen
+    assert!(!lockups.toggle(id, 1).await.unwrap());
+    assert!(lockups.get(id).await.unwrap().unwrap().confirmed);
+
+    assert_eq!(
+        audit_log
+            .get_entries(start(), start() + TimeDelta::hours(1))
+            .await
+            .unwrap(),
+        vec![
+            AuditEntry {
+                at: start(),
+                person,
+                action: AuditAction::LockupStarted,
+                details: "гости в спейсе: Вася".to_string(),
+            },
+            AuditEntry {
+                at: confirmed_at,
+                person,
+                action: AuditAction::LockupConfirmed,
+                details: "отмечено 1/2".to_string(),
+            },
+        ]
+    );
+    assert!(
+        audit_log
+            .get_entries(start() + TimeDelta::hours(1), start() + TimeDelta::hours(2))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
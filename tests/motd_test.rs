@@ -0,0 +1,54 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use xecut_bot::backend::Uid;
+use xecut_bot::motd::{Motd, Motds};
+
+mod common;
+
+async fn make_motds() -> Motds {
+    Motds::new(common::test_pool().await).unwrap()
+}
+
+fn start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_000_000, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_set_replaces_and_expires() {
+    let motds = make_motds().await;
+    assert_eq!(motds.get(start()).await.unwrap(), None);
+
+    let party = Motd {
+        text: "вечеринка".to_string(),
+        author: Uid::from(1),
+        expires_at: start() + TimeDelta::hours(3),
+    };
+    motds.set(&party).await.unwrap();
+    assert_eq!(motds.get(start()).await.unwrap(), Some(party));
+
+    let quiet = Motd {
+        text: "тихий хакинг".to_string(),
+        author: Uid::from(2),
+        expires_at: start() + TimeDelta::hours(1),
+    };
+    motds.set(&quiet).await.unwrap();
+    assert_eq!(motds.get(start()).await.unwrap(), Some(quiet.clone()));
+    assert_eq!(motds.get(quiet.expires_at).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_clear() {
+    let motds = make_motds().await;
+    assert!(!motds.clear().await.unwrap());
+
+    motds
+        .set(&Motd {
+            text: "вечеринка".to_string(),
+            author: Uid::from(1),
+            expires_at: start(),
+        })
+        .await
+        .unwrap();
+    // expired notes are still cleared
+    assert!(motds.clear().await.unwrap());
+    assert!(!motds.clear().await.unwrap());
+}
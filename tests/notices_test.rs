@@ -0,0 +1,72 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use xecut_bot::backend::Uid;
+use xecut_bot::notices::Notices;
+
+mod common;
+
+async fn make_notices() -> Notices {
+    Notices::new(common::test_pool().await).unwrap()
+}
+
+fn start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_000_000, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_expired_notices_are_hidden() {
+    let notices = make_notices().await;
+    let printer = notices
+        .add(
+            Uid::from(1),
+            "3D-принтер сломан",
+            start(),
+            start() + TimeDelta::days(7),
+        )
+        .await
+        .unwrap();
+    notices
+        .add(
+            Uid::from(2),
+            "уборка холодильника в субботу",
+            start() + TimeDelta::hours(1),
+            start() + TimeDelta::days(2),
+        )
+        .await
+        .unwrap();
+
+    let active = notices.get_notices(start()).await.unwrap();
+    assert_eq!(
+        active.iter().map(|n| n.text.as_str()).collect::<Vec<_>>(),
+        ["3D-принтер сломан", "уборка холодильника в субботу"]
+    );
+    assert_eq!(active[0].id, printer);
+    assert_eq!(active[0].author, Uid::from(1));
+
+    let active = notices
+        .get_notices(start() + TimeDelta::days(2))
+        .await
+        .unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].id, printer);
+}
+
+#[tokio::test]
+async fn test_only_author_removes() {
+    let notices = make_notices().await;
+    let id = notices
+        .add(
+            Uid::from(1),
+            "3D-принтер сломан",
+            start(),
+            start() + TimeDelta::days(7),
+        )
+        .await
+        .unwrap();
+
+    assert!(!notices.remove(id, Uid::from(2)).await.unwrap());
+    assert_eq!(notices.get_notices(start()).await.unwrap().len(), 1);
+
+    assert!(notices.remove(id, Uid::from(1)).await.unwrap());
+    assert!(!notices.remove(id, Uid::from(1)).await.unwrap());
+    assert!(notices.get_notices(start()).await.unwrap().is_empty());
+}
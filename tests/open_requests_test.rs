@@ -0,0 +1,93 @@
+use chrono::{NaiveDate, NaiveTime};
+use xecut_bot::backend::Uid;
+use xecut_bot::open_requests::{OpenRequest, OpenRequests};
+
+mod common;
+
+async fn make_requests() -> OpenRequests {
+    OpenRequests::new(common::test_pool().await).unwrap()
+}
+
+fn day(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 10, d).unwrap()
+}
+
+#[tokio::test]
+async fn test_create_and_get() {
+    let requests = make_requests().await;
+    let guest = Uid::from(10);
+    let time = NaiveTime::from_hms_opt(18, 30, 0);
+
+    let id = requests
+        .create(guest, day(20), time, "хочу попаять")
+        .await
+        .unwrap()
+        .unwrap();
+    // one request per person and day
+    assert_eq!(
+        requests.create(guest, day(20), None, "").await.unwrap(),
+        None
+    );
+    let other = requests
+        .create(guest, day(21), None, "")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let expected = OpenRequest {
+        id,
+        requester: guest,
+        day: day(20),
+        time,
+        comment: "хочу попаять".to_string(),
+        host: None,
+    };
+    assert_eq!(requests.get(id).await.unwrap(), Some(expected.clone()));
+    assert_eq!(requests.get(other).await.unwrap().unwrap().time, None);
+    assert_eq!(
+        requests.get_requests(day(19), day(20)).await.unwrap(),
+        vec![expected]
+    );
+    assert_eq!(
+        requests.get_requests(day(1), day(31)).await.unwrap().len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_only_first_host_wins() {
+    let requests = make_requests().await;
+    let id = requests
+        .create(Uid::from(10), day(20), None, "")
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(requests.set_host(id, Uid::from(1)).await.unwrap());
+    assert!(!requests.set_host(id, Uid::from(2)).await.unwrap());
+    assert_eq!(
+        requests.get(id).await.unwrap().unwrap().host,
+        Some(Uid::from(1))
+    );
+}
+
+#[tokio::test]
+async fn test_remove_allows_asking_again() {
+    let requests = make_requests().await;
+    let guest = Uid::from(10);
+    let id = requests
+        .create(guest, day(20), None, "хочу попаять")
+        .await
+        .unwrap()
+        .unwrap();
+    requests.set_host(id, Uid::from(1)).await.unwrap();
+
+    assert_eq!(requests.remove(guest, day(21)).await.unwrap(), None);
+    let removed = requests.remove(guest, day(20)).await.unwrap().unwrap();
+    assert_eq!(removed.id, id);
+    assert_eq!(removed.host, Some(Uid::from(1)));
+    assert_eq!(requests.get(id).await.unwrap(), None);
+
+    let again = requests.create(guest, day(20), None, "").await.unwrap();
+    assert!(again.is_some_and(|new_id| new_id != id));
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use xecut_bot::backend::Uid;
+use xecut_bot::open_sessions::OpenSessions;
+
+mod common;
+
+async fn make_sessions() -> OpenSessions {
+    OpenSessions::new(common::test_pool().await).unwrap()
+}
+
+fn start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_000_000, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_open_replaces_running_session() {
+    let sessions = make_sessions().await;
+    let closes_at = start() + TimeDelta::hours(4);
+
+    assert_eq!(sessions.get_latest().await.unwrap(), None);
+
+    let first = sessions
+        .open(Uid::from(1), "", start(), closes_at)
+        .await
+        .unwrap();
+    assert!(first.is_open(start()));
+    assert!(!first.is_open(closes_at));
+    assert_eq!(first.last_change(start()), start());
+    assert_eq!(first.last_change(closes_at), closes_at);
+
+    let later = start() + TimeDelta::hours(1);
+    let second = sessions
+        .open(Uid::from(2), "пайка", later, closes_at)
+        .await
+        .unwrap();
+    assert_ne!(first.id, second.id);
+    assert_eq!(sessions.get_latest().await.unwrap(), Some(second.clone()));
+
+    // the first one can't be closed twice
+    assert!(!sessions.close(first.id, later).await.unwrap());
+    assert!(sessions.close(second.id, later).await.unwrap());
+    let closed = sessions.get_latest().await.unwrap().unwrap();
+    assert_eq!(closed.closed_at, Some(later));
+    assert!(!closed.is_open(later));
+}
+
+#[tokio::test]
+async fn test_reminders_and_expiry() {
+    let sessions = make_sessions().await;
+    let closes_at = start() + TimeDelta::hours(4);
+    let session = sessions
+        .open(Uid::from(1), "", start(), closes_at)
+        .await
+        .unwrap();
+
+    let before_reminder = closes_at - TimeDelta::minutes(30);
+    assert!(
+        sessions
+            .get_due_reminders(before_reminder)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        sessions.get_due_reminders(closes_at).await.unwrap(),
+        vec![session.clone()]
+    );
+    sessions.mark_reminded(session.id).await.unwrap();
+    assert!(
+        sessions
+            .get_due_reminders(closes_at)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // extending resets the reminder
+    let extended = closes_at + TimeDelta::hours(1);
+    assert!(sessions.extend(session.id, extended).await.unwrap());
+    assert_eq!(sessions.get_due_reminders(extended).await.unwrap().len(), 1);
+
+    assert!(sessions.close_expired(closes_at).await.unwrap().is_empty());
+    let expired = sessions.close_expired(extended).await.unwrap();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].closed_at, Some(extended));
+    assert!(sessions.close_expired(extended).await.unwrap().is_empty());
+    assert!(!sessions.extend(session.id, extended).await.unwrap());
+}
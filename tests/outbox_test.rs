@@ -0,0 +1,156 @@
+use chrono::NaiveDate;
+use xecut_bot::backend::Uid;
+use xecut_bot::outbox::{Announcement, Outbox};
+use xecut_bot::visits::VisitUpdate;
+use xecut_bot::{VisitStatus, Visits};
+
+mod common;
+
+async fn make_visits_and_outbox() -> (Visits, Outbox) {
+    let pool = common::test_pool().await;
+    let visits = Visits::new(pool.clone()).unwrap();
+    let outbox = Outbox::new(pool.clone()).unwrap();
+    (visits, outbox)
+}
+
+#[tokio::test]
+async fn test_upsert_enqueues_only_on_change() {
+    let (visits, outbox) = make_visits_and_outbox().await;
+    let update = VisitUpdate {
+        person: Uid::from(1),
+        day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+        purpose: Some("work".to_string()),
+        status: VisitStatus::CheckedIn,
+    };
+    let announcement = Announcement::CheckIn(update.clone());
+
+    assert!(
+        visits
+            .upsert_visit_announced(&update, &announcement)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !visits
+            .upsert_visit_announced(&update, &announcement)
+            .await
+            .unwrap()
+    );
+
+    let due = outbox.get_due(0).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].announcement, announcement);
+    assert_eq!(due[0].attempts, 0);
+}
+
+#[tokio::test]
+async fn test_delete_enqueues_unplan() {
+    let (visits, outbox) = make_visits_and_outbox().await;
+    let person = Uid::from(2);
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    let announcement = Announcement::Unplan { person, day };
+
+    assert!(
+        !visits
+            .delete_visit_announced(person, day, &announcement)
+            .await
+            .unwrap()
+    );
+    assert_eq!(outbox.get_due(0).await.unwrap(), vec![]);
+
+    visits
+        .upsert_visit(&VisitUpdate {
+            person,
+            day,
+            purpose: None,
+            status: VisitStatus::Planned,
+        })
+        .await
+        .unwrap();
+    assert!(
+        visits
+            .delete_visit_announced(person, day, &announcement)
+            .await
+            .unwrap()
+    );
+    let due = outbox.get_due(0).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].announcement, announcement);
+}
+
+#[tokio::test]
+async fn test_reschedule_and_delete() {
+    let (visits, outbox) = make_visits_and_outbox().await;
+    let update = VisitUpdate {
+        person: Uid::from(3),
+        day: NaiveDate::from_ymd_opt(2025, 8, 9).unwrap(),
+        purpose: None,
+        status: VisitStatus::Planned,
+    };
+    visits
+        .upsert_visit_announced(&update, &Announcement::Plan(update.clone()))
+        .await
+        .unwrap();
+    let id = outbox.get_due(0).await.unwrap()[0].id;
+
+    outbox.reschedule(id, 100).await.unwrap();
+    assert_eq!(outbox.get_due(99).await.unwrap(), vec![]);
+    let due = outbox.get_due(100).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].attempts, 1);
+
+    outbox.delete(id).await.unwrap();
+    assert_eq!(outbox.get_due(100).await.unwrap(), vec![]);
+}
+
+#[tokio::test]
+async fn test_failing_entry_only_blocks_the_same_visit() {
+    let (visits, outbox) = make_visits_and_outbox().await;
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    let plan = |person: i64| VisitUpdate {
+        person: Uid::from(person),
+        day,
+        purpose: None,
+        status: VisitStatus::Planned,
+    };
+    for update in [plan(1), plan(2)] {
+        visits
+            .upsert_visit_announced(&update, &Announcement::Plan(update.clone()))
+            .await
+            .unwrap();
+    }
+    let unplan = Announcement::Unplan {
+        person: Uid::from(1),
+        day,
+    };
+    visits
+        .delete_visit_announced(Uid::from(1), day, &unplan)
+        .await
+        .unwrap();
+
+    // the unplan waits for the plan of the same visit
+    let due = outbox.get_due(0).await.unwrap();
+    assert_eq!(
+        due.iter().map(|e| &e.announcement).collect::<Vec<_>>(),
+        [&Announcement::Plan(plan(1)), &Announcement::Plan(plan(2))]
+    );
+
+    // the first one keeps failing, the others are still delivered
+    for _ in 0..3 {
+        outbox.reschedule(due[0].id, 100).await.unwrap();
+    }
+    let due_now = outbox.get_due(0).await.unwrap();
+    assert_eq!(due_now.len(), 1);
+    assert_eq!(due_now[0].announcement, Announcement::Plan(plan(2)));
+    outbox.delete(due_now[0].id).await.unwrap();
+    assert_eq!(outbox.get_due(0).await.unwrap(), vec![]);
+
+    let retried = outbox.get_due(100).await.unwrap();
+    assert_eq!(retried.len(), 1);
+    assert_eq!(retried[0].attempts, 3);
+
+    outbox.delete(retried[0].id).await.unwrap();
+    let due = outbox.get_due(0).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].announcement, unplan);
+}
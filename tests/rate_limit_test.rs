@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use teloxide::types::ChatId;
+use tokio::time::timeout;
+use xecut_bot::rate_limit::{Priority, RateLimiter};
+
+const BUSY_GROUP: ChatId = ChatId(-1001);
+const OTHER_GROUP: ChatId = ChatId(-1002);
+
+/// Fills the per-minute window of a group chat
+async fn fill_group_window(limiter: &RateLimiter, chat: ChatId) {
+    for _ in 0..20 {
+        limiter.acquire(Priority::High, Some(chat)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_busy_chat_does_not_block_low_priority_elsewhere() {
+    let limiter = Arc::new(RateLimiter::new());
+    fill_group_window(&limiter, BUSY_GROUP).await;
+
+    // waits for the busy chat's window for up to a minute
+    let high = tokio::spawn({
+        let limiter = limiter.clone();
+        async move { limiter.acquire(Priority::High, Some(BUSY_GROUP)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(limiter.metrics().waiting_high, 1);
+
+    assert!(
+        timeout(
+            Duration::from_secs(1),
+            limiter.acquire(Priority::Low, Some(OTHER_GROUP))
+        )
+        .await
+        .is_ok()
+    );
+    // the same chat still lets the high priority request go first
+    assert!(
+        timeout(
+            Duration::from_millis(300),
+            limiter.acquire(Priority::Low, Some(BUSY_GROUP))
+        )
+        .await
+        .is_err()
+    );
+
+    high.abort();
+    let _ = high.await;
+}
+
+#[tokio::test]
+async fn test_cancelled_request_stops_waiting() {
+    let limiter = RateLimiter::new();
+    fill_group_window(&limiter, BUSY_GROUP).await;
+
+    assert!(
+        timeout(
+            Duration::from_millis(100),
+            limiter.acquire(Priority::High, Some(BUSY_GROUP))
+        )
+        .await
+        .is_err()
+    );
+    assert_eq!(limiter.metrics().waiting_high, 0);
+    assert_eq!(limiter.metrics().waiting_low, 0);
+
+    // nothing is waiting anymore, so low priority requests go through right away
+    assert!(
+        timeout(
+            Duration::from_millis(50),
+            limiter.acquire(Priority::Low, None)
+        )
+        .await
+        .is_ok()
+    );
+}
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+use xecut_bot::backend::Uid;
+use xecut_bot::stats::{StatsPeriod, TopVisitor, compute_stats};
+use xecut_bot::visits::VisitUpdate;
+use xecut_bot::{Visit, VisitStatus, Visits};
+
+mod common;
+
+fn visit(person: i64, day: NaiveDate, status: VisitStatus) -> Visit {
+    Visit {
+        person: Uid::from(person),
+        day,
+        purpose: String::new(),
+        status,
+    }
+}
+
+#[test]
+fn test_period() {
+    let today = NaiveDate::from_ymd_opt(2025, 8, 10).unwrap();
+    assert_eq!(StatsPeriod::parse(""), Some(StatsPeriod::Week));
+    assert_eq!(StatsPeriod::parse("Месяц"), Some(StatsPeriod::Month));
+    assert_eq!(StatsPeriod::parse("year"), Some(StatsPeriod::Year));
+    assert_eq!(StatsPeriod::parse("век"), None);
+    assert_eq!(
+        StatsPeriod::Week.range(today),
+        (NaiveDate::from_ymd_opt(2025, 8, 4).unwrap(), today)
+    );
+}
+
+#[test]
+fn test_compute_stats() {
+    // Monday
+    let from = NaiveDate::from_ymd_opt(2025, 8, 4).unwrap();
+    let to = NaiveDate::from_ymd_opt(2025, 8, 10).unwrap();
+    let friday = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    let saturday = NaiveDate::from_ymd_opt(2025, 8, 9).unwrap();
+    let visits = vec![
+        visit(1, from, VisitStatus::CheckedOut),
+        visit(1, friday, VisitStatus::CheckedOut),
+        visit(2, friday, VisitStatus::CheckedOut),
+        visit(3, friday, VisitStatus::CheckedIn),
+        visit(2, saturday, VisitStatus::CheckedOut),
+        // plans and visits outside of the period are ignored
+        visit(4, saturday, VisitStatus::Planned),
+        visit(5, to.succ_opt().unwrap(), VisitStatus::CheckedIn),
+    ];
+    let residents = HashSet::from([Uid::from(1)]);
+
+    let stats = compute_stats(&visits, &residents, from, to);
+    assert_eq!(stats.total_visits, 5);
+    assert_eq!(stats.unique_visitors, 3);
+    assert_eq!(stats.resident_visits, 2);
+    assert_eq!(stats.guest_visits, 3);
+    assert_eq!(stats.visits_by_weekday, [1, 0, 0, 0, 3, 1, 0]);
+    assert!((stats.average_per_evening - 5.0 / 7.0).abs() < 1e-9);
+    assert_eq!(
+        stats.top_visitors,
+        vec![
+            TopVisitor {
+                person: 1,
+                visits: 2
+            },
+            TopVisitor {
+                person: 2,
+                visits: 2
+            },
+            TopVisitor {
+                person: 3,
+                visits: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_compute_stats_empty() {
+    let day = NaiveDate::from_ymd_opt(2025, 8, 4).unwrap();
+    let stats = compute_stats(&[], &HashSet::new(), day, day);
+    assert_eq!(stats.total_visits, 0);
+    assert_eq!(stats.average_per_evening, 0.0);
+    assert_eq!(stats.top_visitors, vec![]);
+}
+
+#[tokio::test]
+async fn test_checking_out_without_checking_in_is_not_a_visit() {
+    let visits = Visits::new(common::test_pool().await).unwrap();
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    let update = |person: i64, status| VisitUpdate {
+        person: Uid::from(person),
+        day,
+        purpose: None,
+        status,
+    };
+    // came and left
+    visits
+        .upsert_visit(&update(1, VisitStatus::CheckedIn))
+        .await
+        .unwrap();
+    // planned, then pressed "Я ушёл" without ever coming
+    visits
+        .upsert_visit(&update(2, VisitStatus::Planned))
+        .await
+        .unwrap();
+    for person in 1..=3 {
+        visits.check_out(Uid::from(person), day).await.unwrap();
+    }
+    visits.check_out_everybody(day).await.unwrap();
+
+    let stored = visits.get_visits(day, day).await.unwrap();
+    let stats = compute_stats(&stored, &HashSet::new(), day, day);
+    assert_eq!(stats.total_visits, 1);
+    assert_eq!(stats.unique_visitors, 1);
+    assert_eq!(
+        stored
+            .iter()
+            .map(|v| (i64::from(v.person), v.status))
+            .collect::<Vec<_>>(),
+        vec![(1, VisitStatus::CheckedOut), (2, VisitStatus::Planned)]
+    );
+}
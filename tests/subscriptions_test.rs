@@ -0,0 +1,84 @@
+use chrono::NaiveTime;
+use xecut_bot::backend::Uid;
+use xecut_bot::subscriptions::{QuietHours, Subscription, Subscriptions};
+
+mod common;
+
+async fn make_subscriptions() -> Subscriptions {
+    Subscriptions::new(common::test_pool().await).unwrap()
+}
+
+fn time(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+#[test]
+fn test_quiet_hours_contains() {
+    let day = QuietHours {
+        from: time(13, 0),
+        to: time(15, 30),
+    };
+    assert!(day.contains(time(13, 0)));
+    assert!(day.contains(time(15, 29)));
+    assert!(!day.contains(time(15, 30)));
+    assert!(!day.contains(time(12, 59)));
+
+    let night = QuietHours {
+        from: time(23, 0),
+        to: time(9, 0),
+    };
+    assert!(night.contains(time(23, 30)));
+    assert!(night.contains(time(0, 0)));
+    assert!(night.contains(time(8, 59)));
+    assert!(!night.contains(time(9, 0)));
+    assert!(!night.contains(time(22, 59)));
+}
+
+#[tokio::test]
+async fn test_upsert_and_get_subscriptions() {
+    let subscriptions = make_subscriptions().await;
+    let subscription = Subscription {
+        person: Uid::from(1),
+        notify_close: false,
+        quiet_hours: None,
+    };
+    subscriptions
+        .upsert_subscription(&subscription)
+        .await
+        .unwrap();
+    assert_eq!(
+        subscriptions.get_subscriptions().await.unwrap(),
+        vec![subscription]
+    );
+
+    let updated = Subscription {
+        person: Uid::from(1),
+        notify_close: true,
+        quiet_hours: Some(QuietHours {
+            from: time(23, 0),
+            to: time(9, 0),
+        }),
+    };
+    subscriptions.upsert_subscription(&updated).await.unwrap();
+    assert_eq!(
+        subscriptions.get_subscriptions().await.unwrap(),
+        vec![updated]
+    );
+}
+
+#[tokio::test]
+async fn test_delete_subscription() {
+    let subscriptions = make_subscriptions().await;
+    let person = Uid::from(2);
+    assert!(!subscriptions.delete_subscription(person).await.unwrap());
+    subscriptions
+        .upsert_subscription(&Subscription {
+            person,
+            notify_close: false,
+            quiet_hours: None,
+        })
+        .await
+        .unwrap();
+    assert!(subscriptions.delete_subscription(person).await.unwrap());
+    assert_eq!(subscriptions.get_subscriptions().await.unwrap(), vec![]);
+}
@@ -0,0 +1,64 @@
+use chrono::{NaiveTime, TimeZone, Weekday};
+use chrono_tz::Europe::Belgrade;
+use xecut_bot::utils::{next_daily, next_weekly};
+
+#[test]
+fn test_next_weekly() {
+    let time = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+    // Wednesday
+    let now = Belgrade.with_ymd_and_hms(2025, 10, 15, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        next_weekly(&now, Weekday::Mon, time),
+        Belgrade.with_ymd_and_hms(2025, 10, 20, 10, 0, 0).unwrap()
+    );
+    assert_eq!(
+        next_weekly(&now, Weekday::Thu, time),
+        Belgrade.with_ymd_and_hms(2025, 10, 16, 10, 0, 0).unwrap()
+    );
+    // the same day, before and after the time
+    assert_eq!(
+        next_weekly(
+            &now,
+            Weekday::Wed,
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap()
+        ),
+        Belgrade.with_ymd_and_hms(2025, 10, 15, 18, 0, 0).unwrap()
+    );
+    assert_eq!(
+        next_weekly(&now, Weekday::Wed, time),
+        Belgrade.with_ymd_and_hms(2025, 10, 22, 10, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn test_next_daily() {
+    let now = Belgrade.with_ymd_and_hms(2025, 10, 15, 12, 0, 0).unwrap();
+    assert_eq!(
+        next_daily(&now, NaiveTime::from_hms_opt(23, 0, 0).unwrap()),
+        Belgrade.with_ymd_and_hms(2025, 10, 15, 23, 0, 0).unwrap()
+    );
+    // already passed today
+    assert_eq!(
+        next_daily(&now, NaiveTime::from_hms_opt(1, 0, 0).unwrap()),
+        Belgrade.with_ymd_and_hms(2025, 10, 16, 1, 0, 0).unwrap()
+    );
+    assert_eq!(
+        next_daily(&now, NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+        Belgrade.with_ymd_and_hms(2025, 10, 16, 12, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn test_next_weekly_skips_dst_gap() {
+    // clocks jump from 02:00 to 03:00 on Sunday, 30 March 2025
+    let now = Belgrade.with_ymd_and_hms(2025, 3, 29, 12, 0, 0).unwrap();
+    assert_eq!(
+        next_weekly(
+            &now,
+            Weekday::Sun,
+            NaiveTime::from_hms_opt(2, 30, 0).unwrap()
+        ),
+        Belgrade.with_ymd_and_hms(2025, 4, 6, 2, 30, 0).unwrap()
+    );
+}
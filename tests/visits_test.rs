@@ -1,39 +1,12 @@
-use chrono::NaiveDate;
-use sqlx::sqlite::SqlitePool;
+use chrono::{Datelike, NaiveDate};
 use xecut_bot::backend::Uid;
-use xecut_bot::backend::connect_db;
 use xecut_bot::{Visit, VisitStatus, Visits};
 
-fn in_memory_db_config() -> xecut_bot::config::DbConfig {
-    xecut_bot::config::DbConfig {
-        sqlite_path: ":memory:".to_string(),
-    }
-}
-
-async fn setup_schema(pool: &SqlitePool) {
-    sqlx::query(
-        "
-        CREATE TABLE visit (
-            person INTEGER,
-            day INTEGER,
-            purpose TEXT,
-            status INTEGER,
-            PRIMARY KEY (person, day)
-        );
-        ",
-    )
-    .execute(pool)
-    .await
-    .unwrap();
-}
+mod common;
 
 // Helper to create Visits with in-memory DB and apply schema
 async fn make_visits() -> Visits {
-    let cfg = in_memory_db_config();
-    let pool = connect_db(&cfg).await.unwrap();
-    let visits = Visits::new(pool.clone()).unwrap();
-    setup_schema(&pool).await;
-    visits
+    Visits::new(common::test_pool().await).unwrap()
 }
 
 #[tokio::test]
@@ -130,6 +103,95 @@ async fn test_delete_nonexistent_visit() {
     assert!(!deleted);
 }
 
+#[tokio::test]
+async fn test_get_visit() {
+    let visits = make_visits().await;
+    let person = Uid::from(4);
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    assert_eq!(visits.get_visit(person, day).await.unwrap(), None);
+    visits
+        .upsert_visit(&xecut_bot::visits::VisitUpdate {
+            person,
+            day,
+            purpose: Some("solder".to_string()),
+            status: VisitStatus::CheckedIn,
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        visits.get_visit(person, day).await.unwrap(),
+        Some(Visit {
+            person,
+            day,
+            purpose: "solder".to_string(),
+            status: VisitStatus::CheckedIn,
+        })
+    );
+    let other_day = NaiveDate::from_ymd_opt(2025, 8, 9).unwrap();
+    assert_eq!(visits.get_visit(person, other_day).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_update_purpose() {
+    let visits = make_visits().await;
+    let person = Uid::from(5);
+    let day = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    assert!(!visits.update_purpose(person, day, "typo").await.unwrap());
+    visits
+        .upsert_visit(&xecut_bot::visits::VisitUpdate {
+            person,
+            day,
+            purpose: Some("sodler".to_string()),
+            status: VisitStatus::Planned,
+        })
+        .await
+        .unwrap();
+    assert!(visits.update_purpose(person, day, "solder").await.unwrap());
+    assert_eq!(
+        visits.get_visit(person, day).await.unwrap(),
+        Some(Visit {
+            person,
+            day,
+            purpose: "solder".to_string(),
+            status: VisitStatus::Planned,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_get_person_visits() {
+    let visits = make_visits().await;
+    let person = Uid::from(6);
+    let day1 = NaiveDate::from_ymd_opt(2025, 8, 8).unwrap();
+    let day2 = NaiveDate::from_ymd_opt(2025, 8, 10).unwrap();
+    let day3 = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+    for (person, day) in [
+        (person, day2),
+        (person, day1),
+        (Uid::from(7), day1),
+        (person, day3),
+    ] {
+        visits
+            .upsert_visit(&xecut_bot::visits::VisitUpdate {
+                person,
+                day,
+                purpose: None,
+                status: VisitStatus::Planned,
+            })
+            .await
+            .unwrap();
+    }
+
+    let days = visits
+        .get_person_visits(person, day1, day2)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| (v.person, v.day))
+        .collect::<Vec<_>>();
+    assert_eq!(days, vec![(person, day1), (person, day2)]);
+}
+
 #[tokio::test]
 async fn test_cleanup() {
     let visits = make_visits().await;
@@ -238,3 +300,34 @@ async fn test_get_visits_range() {
     let visits_none = visits.get_visits(day3, day3).await.unwrap();
     assert_eq!(visits_none, vec![]);
 }
+
+#[tokio::test]
+async fn test_cleanup_keeps_history_days() {
+    let visits = make_visits().await.with_history_days(10);
+    let today = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+    assert_eq!(
+        visits.history_start(today),
+        NaiveDate::from_ymd_opt(2025, 8, 10).unwrap()
+    );
+    for day in [5, 10, 15] {
+        visits
+            .upsert_visit(&xecut_bot::visits::VisitUpdate {
+                person: Uid::from(1),
+                day: NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+                purpose: None,
+                status: VisitStatus::CheckedOut,
+            })
+            .await
+            .unwrap();
+    }
+
+    visits.cleanup(today).await.unwrap();
+    let kept = visits
+        .get_visits(NaiveDate::MIN, NaiveDate::MAX)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.day.day())
+        .collect::<Vec<_>>();
+    assert_eq!(kept, [10, 15]);
+}